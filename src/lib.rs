@@ -1,17 +1,32 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
-use std::{fs, process};
+use std::ffi::{c_char, c_int, c_long, c_ulong, c_ushort, CStr, OsStr};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::iter;
+use std::ops::AddAssign;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fmt, fs};
+use std::{ptr, slice};
 
 pub const HELP: &str = "\
 List directory contents.
 Ignore files and directories starting with a '.' by default
 
-Usage: minils [options] [path]
+Usage: minils [options] [paths...]
+Options that take a value accept it as --name=VALUE, --name VALUE, -xVALUE or -x VALUE,
+except --color, whose value is optional and so only follows an '='
 
 META OPTIONS
   -?, --help
           show list of command-line options
-  -v, --version
+  --version
           show version of minils
 
 Display Options
@@ -19,432 +34,4405 @@ Display Options
           display one entry per line
   -l, --long
           display extended file metadata as a table
+  --no-header
+          leave out the column names above long mode tables
+  --no-filename
+          leave the names out of long mode, so only the other columns are left
   -G, --grid
           display entries as a grid (default)
+  -x, --across
+          display entries as a grid filled row by row instead of column by column
+  -m, --comma
+          display entries as a comma-separated list, wrapped at the width
+          the last of -1, -l, -G, -x and -m wins
+  -w, --width=COLS
+          wrap the grid and -m lists at COLS columns instead of $COLUMNS or
+          the terminal's width
+  --gutter=N
+          leave N spaces, up to 255, between grid and long mode columns instead
+          of 2
+  -R, --recurse
+          recurse into directories
+  --max-depth=N
+          with -R, list at most N levels, counting the given directory as 1
+  --mounts
+          with -R, --tree or --find, don't descend into directories on other
+          filesystems than the given directory
+  --tree
+          show directories recursively as a tree
+  --tree-depth=N
+          like --tree, but at most N levels deep
+  -h, --human-readable
+          show sizes in long mode with binary units (KiB, MiB, ...)
+  --bytes
+          show exact sizes in bytes in long mode, overriding -h
+  --allocated-size
+          show how much disk space files take up in long mode rather than their
+          length, which is less for sparse files
+  --apparent-size
+          show the length of files in long mode (default); the last of these
+          two options wins
+  --du
+          show the total size of each directory's contents in long mode, which
+          means reading everything under it
+  --dir-counts
+          show how many entries each directory holds in long mode, in place of
+          its size; --du wins
+  -i, --inode
+          show the inode number of each entry
+  -s, --size
+          show the space allocated to each entry, in 1K blocks
+  --blocksize=SIZE, --block-size=SIZE
+          count -s and long mode totals in blocks of SIZE, like 4K or M,
+          instead of 1K; also read from the BLOCK_SIZE environment variable
+  -o, --octal-permissions
+          show permissions as an octal number too in long mode
+  -n, --numeric
+          show user and group IDs instead of names in long mode
+  --author
+          show the author of each file in long mode, which is always its owner
+  --total
+          finish with a line counting the files and directories listed and their total size
+  --json
+          print the listing as a JSON array of entries, for scripts
+  -0, --zero
+          print only names, each ending in a NUL byte, for xargs -0
+  --raw
+          print only names, one per line, ignoring every other display option
+  --summary
+          print how many entries of each type there are, their total size and
+          the largest and newest files instead of the entries
+  --count-only
+          print only how many entries would be listed, following -R and
+          --max-entries like the listing does
+  --pager
+          page the listing through $PAGER, or less, when writing to a terminal
+  --link-target, --show-target
+          show what symlinks point to outside long mode too, describing the
+          links themselves rather than their targets, unlike -L
+  -L, --dereference
+          show what symlinks point to instead of the links themselves
+  -H, --dereference-command-line
+          like -L, but only for symlinks given as paths
+  -F, --classify
+          append a type indicator to names: one of /*@|=
+  -p
+          append / to directory names only
+  --indicator-style=STYLE
+          which indicators to append to names: none (default), slash (-p)
+          or classify (-F); the last of these options wins
+  --color=WHEN
+          when to use terminal colors: auto (default), always or never.
+          auto disables colors if the NO_COLOR environment variable is set
+  --color-scale
+          color sizes in long mode from faint to red as they grow
+  --icons
+          put an icon before each name, which needs a Nerd Font
+  --hyperlink
+          make names clickable links to their files in terminals that support
+          it; like colors, only when writing to a terminal or with --color=always
+  --time-style=STYLE
+          how long mode shows times: default, long-iso, full-iso, iso or
+          relative, like 3 days ago
+  --quoting-style=STYLE
+          how to print names with spaces or special characters: literal, shell
+          (default on a terminal) or escape
+  -b, --escape
+          print non-graphic characters in names as C-style escapes, like
+          --quoting-style=escape
+
+
+Sorting Options
+  -r, --reverse
+          reverse the sort order. directories grouped by
+          --group-directories-first or -last keep their place and are only
+          reversed among themselves
+  --sort=WORDS
+          sort by WORDS: name (default), time, size, extension, version, type
+          (directories first) or none. several may be given separated by commas,
+          like size,name, with each later one breaking ties of the ones before it
+  -t, --sort-time
+          sort by modification time, newest first
+  -c
+          show and sort by the time of the last status change instead
+  -u
+          show and sort by the time of the last access instead
+  -S, --sort-size
+          sort by file size, largest first (directories count as empty)
+  -X, --sort=extension
+          sort by extension, then by name; entries without one come first
+  -v, --sort=version
+          sort numbers within names by value, so file2 comes before file10
+  -U, --sort=none
+          don't sort; entries are listed in whatever order the filesystem
+          returns them, and -r has no effect
+  --case-sensitive
+          compare names byte by byte, so uppercase sorts before lowercase
+  --group-directories-first
+          list directories before other entries
+  --group-directories-last
+          list directories after other entries
 
 
 Filtering Options
   -a, --all
-          show hidden and 'dot' files
+          show hidden and 'dot' files, including . and ..
+  -A, --almost-all
+          show hidden and 'dot' files, except for . and ..
   -d, --list-dirs
           list directories as files; don't list their contents
   -D, --only-dirs
           list only directories
   -f, --only-files
           list only files
+  --only-links
+          list only symbolic links; can't be combined with -D or -f
+  -I, --ignore=PATTERN
+          don't list entries whose names match the glob PATTERN; may be repeated
+  --find=PATTERN
+          print the path of every entry below the directories whose name
+          matches the glob PATTERN, instead of listing them; --max-depth applies
+  --files-from=FILE
+          also list the paths in FILE, one per line, or in stdin if FILE is -
+  --ext=EXT
+          list only files with the extension EXT, in any case; may be repeated.
+          -R still goes into every directory, and --tree still shows them
+  --newer-than=DURATION
+          list only entries modified within DURATION, a number followed by s, m,
+          h, d or w, like 30m or 7d
+  --older-than=DURATION
+          list only entries modified at least DURATION ago
+  --min-size=SIZE
+          list only files of at least SIZE bytes, optionally followed by K, M,
+          G or T, like 10K. directories are always listed
+  --max-size=SIZE
+          list only files of at most SIZE
+  --max-entries=N
+          list at most the first N entries of each directory, then say how many
+          were left out
 ";
 
-fn err_handling<T, E: Error>(err: E) -> T {
-    eprintln!("{err}");
-    process::exit(1);
+#[cfg(target_os = "linux")]
+const TIOCGWINSZ: c_ulong = 0x5413;
+#[cfg(target_os = "macos")]
+const TIOCGWINSZ: c_ulong = 0x40087468;
+
+#[repr(C)]
+struct WinSize {
+    ws_row: c_ushort,
+    ws_col: c_ushort,
+    ws_xpixel: c_ushort,
+    ws_ypixel: c_ushort,
+}
+
+/// Broken-down time as filled in by `localtime_r`
+#[repr(C)]
+struct Tm {
+    tm_sec: c_int,
+    tm_min: c_int,
+    tm_hour: c_int,
+    tm_mday: c_int,
+    tm_mon: c_int,
+    tm_year: c_int,
+    tm_wday: c_int,
+    tm_yday: c_int,
+    tm_isdst: c_int,
+    tm_gmtoff: c_long,
+    tm_zone: *const c_char,
+}
+
+/// Leading field of `struct passwd`, laid out the same on Linux and macOS. Only ever read
+/// through a pointer returned by `getpwuid`, so the rest of the struct can be left out.
+#[repr(C)]
+struct Passwd {
+    pw_name: *const c_char,
+}
+
+/// Leading field of `struct group`, read through pointers returned by `getgrgid`
+#[repr(C)]
+struct Group {
+    gr_name: *const c_char,
+}
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    fn localtime_r(time: *const c_long, result: *mut Tm) -> *mut Tm;
+    fn getpwuid(uid: u32) -> *const Passwd;
+    fn getgrgid(gid: u32) -> *const Group;
+}
+
+/// Width to lay out for, going by `$COLUMNS` and the terminal on stdout. Whether a layout wants
+/// a width at all when stdout isn't a terminal is up to its caller.
+fn terminal_width() -> usize {
+    width_from(env::var("COLUMNS").ok().as_deref(), tty_width())
+}
+
+/// `columns` if it's a valid width, like ls, or else the terminal's width, or else 80
+fn width_from(columns: Option<&str>, tty_width: Option<usize>) -> usize {
+    columns.and_then(parse_width).or(tty_width).unwrap_or(80)
+}
+
+/// Width of the terminal on stdout, or `None` if stdout isn't one or it won't say
+fn tty_width() -> Option<usize> {
+    let stdout = io::stdout();
+
+    if !stdout.is_terminal() {
+        return None;
+    }
+
+    let mut size = WinSize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    // SAFETY: TIOCGWINSZ only writes a winsize struct through the pointer, which is valid for
+    // the duration of the call
+    if unsafe { ioctl(stdout.as_raw_fd(), TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        return Some(size.ws_col as usize);
+    }
+
+    None
+}
+
+/// Name of the user with the given ID, or `None` if there's no such user
+fn user_name(uid: u32) -> Option<String> {
+    // SAFETY: getpwuid returns either null or a pointer to a static struct whose name is a valid
+    // C string. The name is copied out before anything else can overwrite it.
+    unsafe {
+        let passwd = getpwuid(uid);
+
+        if passwd.is_null() || (*passwd).pw_name.is_null() {
+            return None;
+        }
+
+        Some(
+            CStr::from_ptr((*passwd).pw_name)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+/// Name of the group with the given ID, or `None` if there's no such group
+fn group_name(gid: u32) -> Option<String> {
+    // SAFETY: as for getpwuid in user_name
+    unsafe {
+        let group = getgrgid(gid);
+
+        if group.is_null() || (*group).gr_name.is_null() {
+            return None;
+        }
+
+        Some(
+            CStr::from_ptr((*group).gr_name)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Converts seconds since the Unix epoch into the local calendar time
+fn local_time(seconds: i64) -> Option<Tm> {
+    let time = seconds as c_long;
+    let mut tm = Tm {
+        tm_sec: 0,
+        tm_min: 0,
+        tm_hour: 0,
+        tm_mday: 0,
+        tm_mon: 0,
+        tm_year: 0,
+        tm_wday: 0,
+        tm_yday: 0,
+        tm_isdst: 0,
+        tm_gmtoff: 0,
+        tm_zone: ptr::null(),
+    };
+
+    // SAFETY: both pointers are valid for the duration of the call, and localtime_r is the
+    // reentrant variant that only writes through `result`
+    if unsafe { localtime_r(&time, &mut tm) }.is_null() {
+        return None;
+    }
+
+    Some(tm)
+}
+
+/// Formats a timestamp in the given style, always `style.width()` characters wide. The default
+/// style is the one `ls` uses: `Mon DD HH:MM` for the last six months, `Mon DD  YYYY` for
+/// anything older or in the future.
+fn format_time(time: SystemTime, now: SystemTime, style: &TimeStyle) -> String {
+    const SIX_MONTHS: Duration = Duration::from_secs(365 * 24 * 60 * 60 / 2);
+
+    let (seconds, nanoseconds) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => (since_epoch.as_secs() as i64, since_epoch.subsec_nanos()),
+        Err(err) => (-(err.duration().as_secs() as i64), 0),
+    };
+    let Some(tm) = local_time(seconds) else {
+        return format!("{:>width$}", "?", width = style.width());
+    };
+
+    let year = tm.tm_year + 1900;
+    let month = tm.tm_mon + 1;
+    let recent = now.duration_since(time).is_ok_and(|age| age < SIX_MONTHS);
+
+    match style {
+        TimeStyle::Default if recent => format!(
+            "{} {:>2} {:02}:{:02}",
+            MONTHS[tm.tm_mon as usize], tm.tm_mday, tm.tm_hour, tm.tm_min
+        ),
+        TimeStyle::Default => format!(
+            "{} {:>2} {:>5}",
+            MONTHS[tm.tm_mon as usize], tm.tm_mday, year
+        ),
+        TimeStyle::LongIso => format!(
+            "{year:04}-{month:02}-{:02} {:02}:{:02}",
+            tm.tm_mday, tm.tm_hour, tm.tm_min
+        ),
+        TimeStyle::FullIso => {
+            let offset_minutes = tm.tm_gmtoff / 60;
+            let sign = if offset_minutes < 0 { '-' } else { '+' };
+
+            format!(
+                "{year:04}-{month:02}-{:02} {:02}:{:02}:{:02}.{nanoseconds:09} {sign}{:02}{:02}",
+                tm.tm_mday,
+                tm.tm_hour,
+                tm.tm_min,
+                tm.tm_sec,
+                offset_minutes.abs() / 60,
+                offset_minutes.abs() % 60
+            )
+        }
+        TimeStyle::Iso if recent => format!(
+            "{month:02}-{:02} {:02}:{:02}",
+            tm.tm_mday, tm.tm_hour, tm.tm_min
+        ),
+        TimeStyle::Iso => format!("{year:04}-{month:02}-{:02} ", tm.tm_mday),
+        TimeStyle::Relative => format!("{:>width$}", format_age(time, now), width = style.width()),
+    }
+}
+
+/// How long before `now` a time was, in its largest whole unit, like `2 hours ago`. Months are
+/// 30 days and years 365.
+fn format_age(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    // Clock skew, or a timestamp set by hand
+    let Ok(age) = now.duration_since(time) else {
+        return "in the future".to_string();
+    };
+    let seconds = age.as_secs();
+
+    if seconds < 10 {
+        return "just now".to_string();
+    }
+
+    let (unit, length) = UNITS
+        .into_iter()
+        .find(|&(_, length)| seconds >= length)
+        .unwrap_or(UNITS[UNITS.len() - 1]);
+    let count = seconds / length;
+    let plural = if count == 1 { "" } else { "s" };
+
+    format!("{count} {unit}{plural} ago")
+}
+
+#[derive(Debug)]
+pub enum MinilsError {
+    /// An option that isn't recognised
+    InvalidOption(String),
+    /// A lone '-' with no option after it
+    MissingOption,
+    /// An option that takes an argument given without one
+    MissingArgument(String),
+    /// An option with a value it can't make sense of, along with what it expects
+    InvalidValue(String, &'static str),
+    /// Two options that can't be used together
+    ConflictingOptions(String, String),
+    /// An option that only works along with another one
+    RequiresOption(String, String),
+    /// A path operand that doesn't exist or can't be looked at
+    CannotAccess(PathBuf, io::Error),
+    /// A directory that can't be read, so its entries can't be listed
+    CannotOpenDir(PathBuf, io::Error),
+    /// A directory that a followed link leads back into while it's still being listed
+    AlreadyListed(PathBuf),
+    /// A glob operand that matches nothing
+    NoMatches(String),
+    /// An I/O failure, along with what was being done when it happened
+    Failed(String, io::Error),
+    /// Any other I/O failure while listing, which is almost always writing the listing out
+    Io(io::Error),
+}
+
+impl fmt::Display for MinilsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinilsError::InvalidOption(option) => write!(
+                f,
+                "{option}: Invalid option. For help, try running 'minils --help'"
+            ),
+            MinilsError::MissingOption => write!(
+                f,
+                "Option not specified. For help, try running 'minils --help'"
+            ),
+            MinilsError::MissingArgument(option) => write!(
+                f,
+                "{option}: Option requires an argument. For help, try running 'minils --help'"
+            ),
+            MinilsError::InvalidValue(option, expected) => write!(
+                f,
+                "{option}: Invalid value, expected {expected}. For help, try running 'minils --help'"
+            ),
+            MinilsError::ConflictingOptions(first, second) => write!(
+                f,
+                "{first} and {second} can't be used together. For help, try running 'minils --help'"
+            ),
+            MinilsError::RequiresOption(option, required) => write!(
+                f,
+                "{option} only works with {required}. For help, try running 'minils --help'"
+            ),
+            MinilsError::CannotAccess(path, err) => {
+                write!(f, "cannot access '{}': {}", path.display(), describe(err))
+            }
+            MinilsError::CannotOpenDir(path, err) => write!(
+                f,
+                "cannot open directory '{}': {}",
+                path.display(),
+                describe(err)
+            ),
+            MinilsError::AlreadyListed(path) => write!(
+                f,
+                "{}: not listing already-listed directory",
+                path.display()
+            ),
+            MinilsError::NoMatches(pattern) => write!(f, "{pattern}: No matches found"),
+            MinilsError::Failed(doing, err) => write!(f, "{doing}: {}", describe(err)),
+            MinilsError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for MinilsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinilsError::CannotAccess(_, err)
+            | MinilsError::CannotOpenDir(_, err)
+            | MinilsError::Failed(_, err)
+            | MinilsError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Reports an error that only affects part of the listing, which goes on without it
+fn report(doing: String, err: io::Error) {
+    eprintln!("minils: {}", MinilsError::Failed(doing, err));
+}
+
+/// The message of an I/O error without the "(os error N)" that std appends to OS errors
+fn describe(err: &io::Error) -> String {
+    let message = err.to_string();
+
+    match message.rfind(" (os error ") {
+        Some(suffix) => message[..suffix].to_string(),
+        None => message,
+    }
+}
+
+impl From<io::Error> for MinilsError {
+    fn from(err: io::Error) -> Self {
+        MinilsError::Io(err)
+    }
+}
+
+#[derive(PartialEq)]
+pub enum SortKey {
+    Name,
+    Time,
+    Size,
+    Extension,
+    /// Numbers within names compare by value, `-v`
+    Version,
+    /// Directories before everything else
+    Type,
+}
+
+/// How entries are laid out. `-1`, `-l`, `-G`, `-x` and `-m` each pick one, and the last one
+/// given wins.
+#[derive(PartialEq)]
+pub enum DisplayMode {
+    Grid,
+    /// A grid filled row by row rather than column by column, `-x`
+    Across,
+    Oneline,
+    Long,
+    /// Names separated by commas, wrapped at the width like a paragraph
+    Comma,
+}
+
+/// Which timestamp long mode shows and `--sort-time` sorts by
+pub enum TimeField {
+    Modified,
+    /// Last status change, `-c`
+    Changed,
+    /// Last access, `-u`
+    Accessed,
+}
+
+impl TimeField {
+    fn of(&self, metadata: &fs::Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Modified => metadata.modified().ok(),
+            TimeField::Accessed => metadata.accessed().ok(),
+            // std has no accessor for ctime, so it's built from the raw seconds
+            TimeField::Changed => {
+                let seconds = metadata.ctime();
+                let since_epoch =
+                    Duration::new(seconds.unsigned_abs(), metadata.ctime_nsec() as u32);
+
+                if seconds < 0 {
+                    UNIX_EPOCH.checked_sub(since_epoch)
+                } else {
+                    UNIX_EPOCH.checked_add(since_epoch)
+                }
+            }
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            TimeField::Modified => "Modified",
+            TimeField::Changed => "Changed",
+            TimeField::Accessed => "Accessed",
+        }
+    }
+}
+
+/// How `--long` shows timestamps, chosen with `--time-style`
+pub enum TimeStyle {
+    /// `Mon DD HH:MM`, or `Mon DD  YYYY` outside the last six months
+    Default,
+    /// `YYYY-MM-DD HH:MM`
+    LongIso,
+    /// `YYYY-MM-DD HH:MM:SS.NNNNNNNNN +ZZZZ`
+    FullIso,
+    /// `MM-DD HH:MM`, or `YYYY-MM-DD` outside the last six months
+    Iso,
+    /// How long ago, like `3 days ago`
+    Relative,
+}
+
+impl TimeStyle {
+    /// Width of every timestamp in this style
+    fn width(&self) -> usize {
+        match self {
+            TimeStyle::Default => 12,
+            TimeStyle::LongIso => 16,
+            TimeStyle::FullIso => 35,
+            TimeStyle::Iso => 11,
+            // "59 minutes ago"
+            TimeStyle::Relative => 14,
+        }
+    }
+}
+
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+/// What gets appended to names to show their type. `-p` and `-F` each pick one, and the last one
+/// given wins.
+#[derive(PartialEq)]
+pub enum IndicatorStyle {
+    None,
+    /// Only `/` after directories
+    Slash,
+    /// One of `/*@|=`, `--classify`
+    Classify,
+}
+
+/// How names with spaces, shell metacharacters or control characters are printed
+#[derive(PartialEq)]
+pub enum QuotingStyle {
+    /// Shell on a terminal, literal otherwise
+    Auto,
+    /// Exactly as they are
+    Literal,
+    /// In single quotes where a shell would need them, with control characters shown as `?`
+    Shell,
+    /// With backslash escapes, like a C string
+    Escape,
 }
 
 pub struct DisplayOptions {
-    pub oneline: bool,
-    pub grid: bool,
-    pub long: bool,
+    pub mode: DisplayMode,
     pub recurse: bool,
+    /// How many levels `--recurse` lists, counting the operand itself, or `None` for no limit
+    pub max_depth: Option<usize>,
+    /// Don't descend into directories on another filesystem than the operand
+    pub mounts: bool,
+    pub reverse: bool,
+    /// Keys to sort by, each one breaking the ties of those before it. Empty for `-U`, which
+    /// leaves entries in whatever order the filesystem returns.
+    pub sort_keys: Vec<SortKey>,
+    pub group_dirs_first: bool,
+    pub group_dirs_last: bool,
+    pub case_sensitive: bool,
+    pub time_field: TimeField,
+    pub time_style: TimeStyle,
+    pub color: ColorMode,
+    pub quoting_style: QuotingStyle,
+    pub indicator_style: IndicatorStyle,
+    pub numeric_uid_gid: bool,
+    pub author: bool,
+    pub inode: bool,
+    pub blocks: bool,
+    /// Bytes per block for `--size` and long mode's total, from `--blocksize` or `$BLOCK_SIZE`
+    pub block_size: u64,
+    pub human_readable: bool,
+    pub bytes: bool,
+    pub du: bool,
+    /// Show how many entries directories hold in place of their size
+    pub dir_counts: bool,
+    /// Color sizes by how large they are
+    pub color_scale: bool,
+    /// Put a Nerd Font icon before each name
+    pub icons: bool,
+    /// Make names links to their files, for terminals that support OSC 8
+    pub hyperlink: bool,
+    pub allocated_size: bool,
+    pub octal_permissions: bool,
+    pub dereference: bool,
+    pub dereference_command_line: bool,
+    pub tree: bool,
+    /// How many levels below each directory `--tree` goes, or `None` for no limit
+    pub tree_depth: Option<usize>,
+    /// How many entries each directory listing stops at, or `None` for no limit
+    pub max_entries: Option<usize>,
+    /// Width the grid is laid out for, or `None` to use the terminal's
+    pub width: Option<usize>,
+    /// Spaces between grid columns and between the columns of long mode
+    pub gutter: usize,
+    pub json: bool,
+    pub zero: bool,
+    pub raw: bool,
+    /// Print statistics about the entries instead of the entries
+    pub summary: bool,
+    /// Print how many entries pass the filters instead of the entries
+    pub count_only: bool,
+    /// Show what symlinks point to outside long mode too
+    pub link_target: bool,
+    pub total: bool,
+    pub no_header: bool,
+    /// Leave the name column out of long mode
+    pub no_filename: bool,
+    pub pager: bool,
 }
 
 pub struct FilteringOptions {
     pub all: bool,
+    pub almost_all: bool,
     pub list_dirs: bool,
     pub only_dirs: bool,
     pub only_files: bool,
+    pub only_links: bool,
+    pub ignore: Vec<String>,
+    /// Lowercase and without the leading dot
+    pub extensions: Vec<String>,
+    /// Only entries modified at most this long ago
+    pub newer_than: Option<Duration>,
+    /// Only entries modified at least this long ago
+    pub older_than: Option<Duration>,
+    /// Only files of at least this many bytes. Directories are exempt from both size limits.
+    pub min_size: Option<u64>,
+    /// Only files of at most this many bytes
+    pub max_size: Option<u64>,
+    /// Search the whole tree for names matching this glob, printing their paths instead
+    pub find: Option<String>,
+}
+
+/// Space allocated to a file in blocks of `--blocksize` bytes, 1K unless set, rounded up. It
+/// can be well below the file's size for sparse files.
+fn blocks(metadata: &fs::Metadata, display_options: &DisplayOptions) -> u64 {
+    // st_blocks always counts 512-byte units
+    (metadata.blocks() * 512).div_ceil(display_options.block_size)
+}
+
+/// Formats a size in bytes using power-of-ten units, always five characters wide, or with
+/// `binary` set, power-of-two units with one decimal place, always nine characters wide.
+fn format_size(size: u64, binary: bool) -> String {
+    if binary {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+        if size < 1024 {
+            return format!("{size:>8}B");
+        }
+
+        let mut scaled = size as f64 / 1024.0;
+        let mut unit = 0;
+        while scaled >= 1024.0 && unit < UNITS.len() - 1 {
+            scaled /= 1024.0;
+            unit += 1;
+        }
+
+        return format!("{scaled:>6.1}{unit}", unit = UNITS[unit]);
+    }
+
+    if size < 10u64.pow(3) {
+        format!("{size:>4}B")
+    } else if size < 10u64.pow(6) {
+        format!("{size:>3}KB", size = size / 10u64.pow(3))
+    } else if size < 10u64.pow(9) {
+        format!("{size:>3}MB", size = size / 10u64.pow(6))
+    } else if size < 10u64.pow(12) {
+        format!("{size:>3}GB", size = size / 10u64.pow(9))
+    } else {
+        format!("{size:>3}TB", size = size / 10u64.pow(12))
+    }
 }
 
 pub fn parse_arguments(
-    args: &Vec<String>,
+    args: &[String],
     display_options: &mut DisplayOptions,
     filtering_options: &mut FilteringOptions,
-) -> fs::ReadDir {
+) -> Result<Vec<PathBuf>, MinilsError> {
+    let mut operands: Vec<PathBuf> = Vec::new();
+    // An empty list of paths lists nothing rather than the current directory
+    let mut read_operand_list = false;
+    // Only looked up in the environment if it isn't given here
+    let mut block_size = None;
     let mut args_iter = args.iter();
     args_iter.next(); // No need to check first argument, it is the name of the program
 
-    for (i, element) in args_iter.enumerate() {
-        if element.starts_with("--") {
-            match element.as_str() {
-                "--oneline" => {
-                    display_options.oneline = true;
-                    display_options.grid = false;
-                }
-                "--long" => {
-                    display_options.long = true;
-                    display_options.oneline = true;
-                    display_options.grid = false;
-                }
-                "--grid" => {
-                    display_options.grid = true;
-                    display_options.long = false;
-                    display_options.oneline = false;
-                }
-                "--recurse" => {
-                    display_options.recurse = true;
-                    display_options.grid = false;
-                }
-                "--all" => filtering_options.all = true,
-                "--list-dirs" => filtering_options.list_dirs = true,
-                "--only-dirs" => {
-                    filtering_options.only_dirs = true;
+    // Options and paths may be given in any order
+    while let Some(element) = args_iter.next() {
+        if let Some(option) = element.strip_prefix("--") {
+            // `--name=value` is split up front so every option is matched by name. Options that
+            // need a value and weren't given one with `=` take the next argument instead.
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            let invalid = |value: &str, expected: &'static str| {
+                MinilsError::InvalidValue(format!("--{name}={value}"), expected)
+            };
+
+            match (name, value) {
+                ("oneline", None) => display_options.mode = DisplayMode::Oneline,
+                ("human-readable", None) => display_options.human_readable = true,
+                ("bytes", None) => display_options.bytes = true,
+                ("du", None) => display_options.du = true,
+                ("dir-counts", None) => display_options.dir_counts = true,
+                ("color-scale", None) => display_options.color_scale = true,
+                ("icons", None) => display_options.icons = true,
+                ("hyperlink", None) => display_options.hyperlink = true,
+                ("allocated-size", None) => display_options.allocated_size = true,
+                ("apparent-size", None) => display_options.allocated_size = false,
+                ("inode", None) => display_options.inode = true,
+                ("size", None) => display_options.blocks = true,
+                ("octal-permissions", None) => display_options.octal_permissions = true,
+                ("numeric", None) => display_options.numeric_uid_gid = true,
+                ("author", None) => display_options.author = true,
+                ("long", None) => display_options.mode = DisplayMode::Long,
+                ("grid", None) => display_options.mode = DisplayMode::Grid,
+                ("across", None) => display_options.mode = DisplayMode::Across,
+                ("comma", None) => display_options.mode = DisplayMode::Comma,
+                ("recurse", None) => display_options.recurse = true,
+                ("mounts", None) => display_options.mounts = true,
+                ("classify", None) => display_options.indicator_style = IndicatorStyle::Classify,
+                ("indicator-style", value) => {
+                    let style = option_value(value, &mut args_iter, element)?;
+                    display_options.indicator_style = match style {
+                        "none" => IndicatorStyle::None,
+                        "slash" => IndicatorStyle::Slash,
+                        "classify" => IndicatorStyle::Classify,
+                        other => return Err(invalid(other, "none, slash or classify")),
+                    }
+                }
+                ("reverse", None) => display_options.reverse = true,
+                ("sort-time", None) => display_options.sort_keys = vec![SortKey::Time],
+                ("sort-size", None) => display_options.sort_keys = vec![SortKey::Size],
+                ("case-sensitive", None) => display_options.case_sensitive = true,
+                ("dereference", None) => display_options.dereference = true,
+                ("dereference-command-line", None) => {
+                    display_options.dereference_command_line = true
+                }
+                ("group-directories-first", None) => display_options.group_dirs_first = true,
+                ("group-directories-last", None) => display_options.group_dirs_last = true,
+                ("escape", None) => display_options.quoting_style = QuotingStyle::Escape,
+                ("all", None) => {
+                    filtering_options.all = true;
+                    filtering_options.almost_all = false;
+                }
+                ("almost-all", None) => {
+                    filtering_options.almost_all = true;
                     filtering_options.all = false;
                 }
-                "--only-files" => {
+                ("list-dirs", None) => filtering_options.list_dirs = true,
+                // The last of --only-dirs and --only-files wins, and hidden entries still follow -a
+                ("only-dirs", None) => {
+                    filtering_options.only_dirs = true;
+                    filtering_options.only_files = false;
+                }
+                ("only-files", None) => {
                     filtering_options.only_files = true;
-                    filtering_options.all = false;
+                    filtering_options.only_dirs = false;
+                }
+                ("only-links", None) => filtering_options.only_links = true,
+                ("tree", None) => display_options.tree = true,
+                ("json", None) => display_options.json = true,
+                ("zero", None) => display_options.zero = true,
+                ("raw", None) => display_options.raw = true,
+                ("summary", None) => display_options.summary = true,
+                ("count-only", None) => display_options.count_only = true,
+                ("link-target" | "show-target", None) => display_options.link_target = true,
+                ("total", None) => display_options.total = true,
+                ("no-header", None) => display_options.no_header = true,
+                ("no-filename", None) => display_options.no_filename = true,
+                ("pager", None) => display_options.pager = true,
+                ("color", None | Some("always")) => display_options.color = ColorMode::Always,
+                ("color", Some("auto")) => display_options.color = ColorMode::Auto,
+                ("color", Some("never")) => display_options.color = ColorMode::Never,
+                ("color", Some(other)) => {
+                    return Err(invalid(other, "auto, always or never"));
                 }
-                option => {
-                    eprintln!(
-                        "{}: Invalid option. For help, try running 'minils --help'",
-                        option
+                ("sort", value) => {
+                    let keys = option_value(value, &mut args_iter, element)?;
+                    display_options.sort_keys = if keys == "none" {
+                        Vec::new()
+                    } else {
+                        // Comma-separated, so `size,name` breaks ties in size by name
+                        keys.split(',')
+                            .map(|key| match key {
+                                "name" => Ok(SortKey::Name),
+                                "time" => Ok(SortKey::Time),
+                                "size" => Ok(SortKey::Size),
+                                "extension" => Ok(SortKey::Extension),
+                                "version" => Ok(SortKey::Version),
+                                "type" => Ok(SortKey::Type),
+                                _ => Err(invalid(keys, SORT_KEYS)),
+                            })
+                            .collect::<Result<_, _>>()?
+                    }
+                }
+                ("time-style", value) => {
+                    let style = option_value(value, &mut args_iter, element)?;
+                    display_options.time_style = match style {
+                        "default" => TimeStyle::Default,
+                        "long-iso" => TimeStyle::LongIso,
+                        "full-iso" => TimeStyle::FullIso,
+                        "iso" => TimeStyle::Iso,
+                        "relative" => TimeStyle::Relative,
+                        other => {
+                            return Err(invalid(
+                                other,
+                                "default, long-iso, full-iso, iso or relative",
+                            ))
+                        }
+                    }
+                }
+                ("quoting-style", value) => {
+                    let style = option_value(value, &mut args_iter, element)?;
+                    display_options.quoting_style = match style {
+                        "literal" => QuotingStyle::Literal,
+                        "shell" => QuotingStyle::Shell,
+                        "escape" => QuotingStyle::Escape,
+                        other => return Err(invalid(other, "literal, shell or escape")),
+                    }
+                }
+                ("tree-depth", value) => {
+                    let depth = option_value(value, &mut args_iter, element)?;
+                    display_options.tree = true;
+                    display_options.tree_depth = Some(
+                        depth
+                            .parse()
+                            .map_err(|_| invalid(depth, "a whole number"))?,
+                    );
+                }
+                ("max-depth", value) => {
+                    let depth = option_value(value, &mut args_iter, element)?;
+                    display_options.max_depth = Some(
+                        depth
+                            .parse()
+                            .map_err(|_| invalid(depth, "a whole number"))?,
+                    );
+                }
+                ("max-entries", value) => {
+                    let count = option_value(value, &mut args_iter, element)?;
+                    display_options.max_entries = Some(
+                        count
+                            .parse()
+                            .map_err(|_| invalid(count, "a whole number"))?,
                     );
-                    process::exit(1);
                 }
+                ("width", value) => {
+                    let width = option_value(value, &mut args_iter, element)?;
+                    display_options.width =
+                        Some(parse_width(width).ok_or_else(|| invalid(width, WIDTH_FORMAT))?);
+                }
+                ("gutter", value) => {
+                    let gutter = option_value(value, &mut args_iter, element)?;
+                    display_options.gutter = parse_width(gutter)
+                        .filter(|&gutter| gutter <= MAX_GUTTER)
+                        .ok_or_else(|| invalid(gutter, GUTTER_FORMAT))?;
+                }
+                ("ignore", value) => {
+                    let pattern = option_value(value, &mut args_iter, element)?;
+                    filtering_options.ignore.push(pattern.to_string());
+                }
+                ("find", value) => {
+                    let pattern = option_value(value, &mut args_iter, element)?;
+                    filtering_options.find = Some(pattern.to_string());
+                }
+                ("files-from", value) => {
+                    let source = option_value(value, &mut args_iter, element)?;
+                    operands.extend(read_operands(source)?);
+                    read_operand_list = true;
+                }
+                ("newer-than", value) => {
+                    let duration = option_value(value, &mut args_iter, element)?;
+                    filtering_options.newer_than =
+                        Some(parse_duration(duration).ok_or_else(|| {
+                            MinilsError::InvalidValue(
+                                format!("--{name}={duration}"),
+                                DURATION_FORMAT,
+                            )
+                        })?);
+                }
+                ("older-than", value) => {
+                    let duration = option_value(value, &mut args_iter, element)?;
+                    filtering_options.older_than =
+                        Some(parse_duration(duration).ok_or_else(|| {
+                            MinilsError::InvalidValue(
+                                format!("--{name}={duration}"),
+                                DURATION_FORMAT,
+                            )
+                        })?);
+                }
+                ("blocksize" | "block-size", value) => {
+                    let size = option_value(value, &mut args_iter, element)?;
+                    block_size = Some(parse_block_size(size).ok_or_else(|| {
+                        MinilsError::InvalidValue(format!("--{name}={size}"), SIZE_FORMAT)
+                    })?);
+                }
+                ("min-size", value) => {
+                    let size = option_value(value, &mut args_iter, element)?;
+                    filtering_options.min_size = Some(parse_size(size).ok_or_else(|| {
+                        MinilsError::InvalidValue(format!("--{name}={size}"), SIZE_FORMAT)
+                    })?);
+                }
+                ("max-size", value) => {
+                    let size = option_value(value, &mut args_iter, element)?;
+                    filtering_options.max_size = Some(parse_size(size).ok_or_else(|| {
+                        MinilsError::InvalidValue(format!("--{name}={size}"), SIZE_FORMAT)
+                    })?);
+                }
+                ("ext", value) => {
+                    let extension = option_value(value, &mut args_iter, element)?;
+                    let extension = extension.strip_prefix('.').unwrap_or(extension);
+                    filtering_options.extensions.push(extension.to_lowercase());
+                }
+                _ => return Err(MinilsError::InvalidOption(element.to_string())),
             }
         } else if element.starts_with("-") {
             let options = element.as_bytes();
 
             if options.len() < 2 {
-                eprintln!("Option not specified. For help, try running 'minils --help'");
-                process::exit(1);
+                return Err(MinilsError::MissingOption);
             }
 
-            let mut options_iter = options.iter();
-            options_iter.next();
-
-            for &option in options_iter {
+            for (i, &option) in options.iter().enumerate().skip(1) {
                 match option {
-                    b'1' => {
-                        display_options.oneline = true;
-                        display_options.grid = false;
-                    }
-                    b'l' => {
-                        display_options.long = true;
-                        display_options.oneline = true;
-                        display_options.grid = false;
+                    b'1' => display_options.mode = DisplayMode::Oneline,
+                    b'0' => display_options.zero = true,
+                    b'h' => display_options.human_readable = true,
+                    b'i' => display_options.inode = true,
+                    b's' => display_options.blocks = true,
+                    b'o' => display_options.octal_permissions = true,
+                    b'n' => display_options.numeric_uid_gid = true,
+                    b'l' => display_options.mode = DisplayMode::Long,
+                    b'G' => display_options.mode = DisplayMode::Grid,
+                    b'x' => display_options.mode = DisplayMode::Across,
+                    b'm' => display_options.mode = DisplayMode::Comma,
+                    b'R' => display_options.recurse = true,
+                    b'L' => display_options.dereference = true,
+                    b'H' => display_options.dereference_command_line = true,
+                    b'F' => display_options.indicator_style = IndicatorStyle::Classify,
+                    b'p' => display_options.indicator_style = IndicatorStyle::Slash,
+                    b'b' => display_options.quoting_style = QuotingStyle::Escape,
+                    b'r' => display_options.reverse = true,
+                    b'c' => display_options.time_field = TimeField::Changed,
+                    b'u' => display_options.time_field = TimeField::Accessed,
+                    b't' => display_options.sort_keys = vec![SortKey::Time],
+                    b'S' => display_options.sort_keys = vec![SortKey::Size],
+                    b'X' => display_options.sort_keys = vec![SortKey::Extension],
+                    b'v' => display_options.sort_keys = vec![SortKey::Version],
+                    b'U' => display_options.sort_keys = Vec::new(),
+                    b'a' => {
+                        filtering_options.all = true;
+                        filtering_options.almost_all = false;
                     }
-                    b'G' => {
-                        display_options.grid = true;
-                        display_options.long = false;
-                        display_options.oneline = false;
-                    }
-                    b'R' => {
-                        display_options.recurse = true;
-                        display_options.grid = false;
+                    b'A' => {
+                        filtering_options.almost_all = true;
+                        filtering_options.all = false;
                     }
-                    b'a' => filtering_options.all = true,
                     b'd' => filtering_options.list_dirs = true,
                     b'D' => {
                         filtering_options.only_dirs = true;
                         filtering_options.only_files = false;
-                        filtering_options.all = false;
                     }
                     b'f' => {
                         filtering_options.only_files = true;
                         filtering_options.only_dirs = false;
-                        filtering_options.all = false;
+                    }
+                    // Options with a value take the rest of this argument, or else the next
+                    // one, so nothing after them in this argument is a flag
+                    b'w' => {
+                        let rest = Some(&element[i + 1..]).filter(|rest| !rest.is_empty());
+                        let width = option_value(rest, &mut args_iter, "-w")?;
+                        display_options.width = Some(parse_width(width).ok_or_else(|| {
+                            MinilsError::InvalidValue(format!("-w {width}"), WIDTH_FORMAT)
+                        })?);
+                        break;
+                    }
+                    b'I' => {
+                        let rest = Some(&element[i + 1..]).filter(|rest| !rest.is_empty());
+                        let pattern = option_value(rest, &mut args_iter, "-I")?;
+                        filtering_options.ignore.push(pattern.to_string());
+                        break;
                     }
                     invalid_option => {
-                        eprintln!(
-                            "{}: Invalid option. For help, try running 'minils --help'",
-                            invalid_option as char
-                        );
-                        process::exit(1);
+                        return Err(MinilsError::InvalidOption(
+                            (invalid_option as char).to_string(),
+                        ));
                     }
                 }
             }
-        } else if i == args.len() - 1 {
-            let metadata = fs::metadata(&args[i]).unwrap_or_else(err_handling);
-            let entry_name = &args[i];
-            let entry_color: &str;
-            let reset = "\x1b[0m";
+        } else if is_glob(element) {
+            // Quoted, or from somewhere that doesn't expand globs itself
+            operands.extend(expand_glob(element)?);
+        } else {
+            operands.push(PathBuf::from(element));
+        }
+    }
 
-            if metadata.is_dir() && filtering_options.list_dirs {
-                entry_color = "\x1b[1;34m"; // bold blue
-            } else if metadata.is_dir() {
-                return fs::read_dir(&args[i]).unwrap_or_else(err_handling);
-            } else if metadata.is_symlink() {
-                entry_color = "\x1b[1;96m";
-            } else {
-                entry_color = "\x1b[1m";
-            }
+    if filtering_options.only_links && (filtering_options.only_dirs || filtering_options.only_files)
+    {
+        let other = if filtering_options.only_dirs {
+            "--only-dirs"
+        } else {
+            "--only-files"
+        };
+        return Err(MinilsError::ConflictingOptions(
+            other.to_string(),
+            "--only-links".to_string(),
+        ));
+    }
 
-            if display_options.long {
-                let permissions_mode = metadata.permissions().mode();
-                let read_color = "\x1b[33m"; // yellow
-                let write_color = "\x1b[31m"; // red
-                let execute_color = "\x1b[32m"; // green
-                let is_user = "\x1b[1m"; // bold
+    // Like ls, an invalid $BLOCK_SIZE is ignored rather than an error
+    display_options.block_size = block_size
+        .or_else(|| {
+            env::var("BLOCK_SIZE")
+                .ok()
+                .and_then(|size| parse_block_size(&size))
+        })
+        .unwrap_or(1024);
 
-                println!("\x1b[4mPermissions\x1b[0m  \x1b[4mSize\x1b[0m  \x1b[4mName\x1b[0m");
+    // Outside long mode the name is all there is to show
+    if display_options.no_filename && display_options.mode != DisplayMode::Long {
+        return Err(MinilsError::RequiresOption(
+            "--no-filename".to_string(),
+            "-l".to_string(),
+        ));
+    }
 
-                if metadata.is_dir() {
-                    print!("{entry_color}d{reset}");
-                } else if metadata.is_file() {
-                    print!("{entry_color}-{reset}")
-                } else {
-                    print!("{entry_color}l{reset}")
-                }
+    if display_options.group_dirs_first && display_options.group_dirs_last {
+        return Err(MinilsError::ConflictingOptions(
+            "--group-directories-first".to_string(),
+            "--group-directories-last".to_string(),
+        ));
+    }
 
-                // User permissions
-                if permissions_mode & 0b100_000_000 == 0b100_000_000 {
-                    print!("{is_user}{read_color}r{reset}");
-                } else {
-                    print!("{is_user}{read_color}-{reset}");
-                }
-                if permissions_mode & 0b010_000_000 == 0b010_000_000 {
-                    print!("{is_user}{write_color}w{reset}");
-                } else {
-                    print!("{is_user}{write_color}-{reset}");
-                }
-                if permissions_mode & 0b001_000_000 == 0b001_000_000 {
-                    print!("{is_user}{execute_color}x{reset}");
-                } else {
-                    print!("{is_user}{execute_color}-{reset}");
-                }
+    if operands.is_empty() && !read_operand_list {
+        operands.push(PathBuf::from("."));
+    }
 
-                // Group permissions
-                if permissions_mode & 0b000_100_000 == 0b000_100_000 {
-                    print!("{read_color}r{reset}");
-                } else {
-                    print!("{read_color}-{reset}");
-                }
-                if permissions_mode & 0b000_010_000 == 0b000_010_000 {
-                    print!("{write_color}w{reset}");
-                } else {
-                    print!("{write_color}-{reset}");
-                }
-                if permissions_mode & 0b000_001_000 == 0b000_001_000 {
-                    print!("{execute_color}x{reset}");
-                } else {
-                    print!("{execute_color}-{reset}");
-                }
+    Ok(operands)
+}
 
-                // Other permissions
-                if permissions_mode & 0b000_000_100 == 0b000_000_100 {
-                    print!("{read_color}r{reset}");
-                } else {
-                    print!("{read_color}-{reset}");
-                }
-                if permissions_mode & 0b000_000_010 == 0b000_000_010 {
-                    print!("{write_color}w{reset}");
-                } else {
-                    print!("{write_color}-{reset}");
-                }
-                if permissions_mode & 0b000_000_001 == 0b000_000_001 {
-                    print!("{execute_color}x{reset}");
-                } else {
-                    print!("{execute_color}-{reset}");
-                }
+/// Paths listed one per line in `source`, or in stdin if it's `-`. Blank lines are skipped, and
+/// paths that don't exist are left for `run` to report like any other operand.
+fn read_operands(source: &str) -> Result<Vec<PathBuf>, MinilsError> {
+    let contents = if source == "-" {
+        let mut contents = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut contents)
+            .map_err(|err| MinilsError::Failed("reading paths from stdin".to_string(), err))?;
+        contents
+    } else {
+        fs::read(source).map_err(|err| MinilsError::CannotAccess(PathBuf::from(source), err))?
+    };
+
+    // Split as bytes, since paths don't have to be valid UTF-8
+    Ok(contents
+        .split(|&byte| byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| PathBuf::from(OsStr::from_bytes(line)))
+        .collect())
+}
 
-                print!("{padding:<2}", padding = "");
+/// The value of an option: the one given along with it, or else the next argument
+fn option_value<'a>(
+    given: Option<&'a str>,
+    args_iter: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<&'a str, MinilsError> {
+    match given {
+        Some(value) => Ok(value),
+        None => args_iter
+            .next()
+            .map(String::as_str)
+            .ok_or_else(|| MinilsError::MissingArgument(option.to_string())),
+    }
+}
 
-                let size = metadata.size();
+/// What `--sort` accepts, for error messages
+const SORT_KEYS: &str =
+    "name, time, size, extension, version, type or none, or several separated by commas";
 
-                if !metadata.is_dir() {
-                    if size < 10u64.pow(3) {
-                        print!(" {size:>3}B");
-                    } else if size < 10u64.pow(6) {
-                        print!("{size:>3}KB", size = size / 10u64.pow(6));
-                    } else if size < 10u64.pow(9) {
-                        print!("{size:>3}MB", size = size / 10u64.pow(9));
-                    } else if size < 10u64.pow(12) {
-                        print!("{size:>3}GB", size = size / 10u64.pow(12));
-                    } else {
-                        print!("{size:>3}TB", size = size / 10u64.pow(15));
-                    }
-                } else {
-                    print!("{:>5}", "-");
-                }
-                print!("{padding:<2}", padding = "");
-            }
+/// What `parse_duration` accepts, for error messages
+const DURATION_FORMAT: &str = "a number followed by s, m, h, d or w, like 30m or 7d";
 
-            if display_options.long && metadata.is_symlink() {
-                let real_path = fs::read_link(&args[i]).unwrap_or_else(err_handling);
-                print!(
-                    "{entry_color}{entry_name}{reset} -> {real_path_color}{real_path}{reset}",
-                    real_path = real_path.display(),
-                    real_path_color = "\x1b[0;31m", // regular red
-                );
-            } else {
-                print!("{entry_color}{entry_name}{padding:<5}{reset}", padding = "");
-            }
+/// A duration like `30m` or `7d`: a whole number followed by one of the units s, m, h, d or w
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit = duration.chars().last()?;
+    let count: u64 = duration[..duration.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
 
-            println!();
-            process::exit(0); // hacky solution
-        } else {
-            eprintln!("Error parsing option. For help, try running 'minils --help'");
-            process::exit(1);
-        }
-    }
-    return fs::read_dir(".").unwrap_or_else(err_handling);
+    Some(Duration::from_secs(count.checked_mul(seconds)?))
 }
 
-fn print_entry(
-    entry: &fs::DirEntry,
-    entry_type: &fs::FileType,
-    entry_name: &String,
-    display_options: &DisplayOptions,
-) {
-    let entry_color: &str;
-    let reset = "\x1b[0m";
+/// What `parse_size` accepts, for error messages
+const SIZE_FORMAT: &str = "a number of bytes, optionally followed by K, M, G or T, like 10K";
 
-    if entry_type.is_dir() {
-        entry_color = "\x1b[1;34m"; // bold blue
-    } else if entry_type.is_symlink() {
-        entry_color = "\x1b[1;96m"; // bold cyan
-    } else {
-        entry_color = "\x1b[1m";
-    }
+/// A size like `500`, `10K` or `5M` in bytes. The units are binary, so `1K` is 1024 bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let (count, exponent) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 1),
+        (i, 'M' | 'm') => (&size[..i], 2),
+        (i, 'G' | 'g') => (&size[..i], 3),
+        (i, 'T' | 't') => (&size[..i], 4),
+        _ => (size, 0),
+    };
 
-    if display_options.long {
-        let metadata = entry.metadata().unwrap_or_else(err_handling);
-        let permissions_mode = metadata.permissions().mode();
-        let read_color = "\x1b[33m"; // yellow
-        let write_color = "\x1b[31m"; // red
-        let execute_color = "\x1b[32m"; // green
-        let is_user = "\x1b[1m"; // bold
+    count
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(1024u64.pow(exponent))
+}
 
-        if entry_type.is_dir() {
-            print!("{entry_color}d{reset}");
-        } else if entry_type.is_file() {
-            print!("{entry_color}-{reset}")
-        } else {
-            print!("{entry_color}l{reset}")
-        }
+/// A block size for `--blocksize` or `$BLOCK_SIZE`: a size like `parse_size` takes, or just a
+/// unit, like ls. Zero-byte blocks don't make sense.
+fn parse_block_size(size: &str) -> Option<u64> {
+    parse_size(size)
+        .or_else(|| parse_size(&format!("1{size}")))
+        .filter(|&size| size > 0)
+}
 
-        // User permissions
-        if permissions_mode & 0b100_000_000 == 0b100_000_000 {
-            print!("{is_user}{read_color}r{reset}");
-        } else {
-            print!("{is_user}{read_color}-{reset}");
-        }
-        if permissions_mode & 0b010_000_000 == 0b010_000_000 {
-            print!("{is_user}{write_color}w{reset}");
-        } else {
-            print!("{is_user}{write_color}-{reset}");
-        }
-        if permissions_mode & 0b001_000_000 == 0b001_000_000 {
-            print!("{is_user}{execute_color}x{reset}");
-        } else {
-            print!("{is_user}{execute_color}-{reset}");
-        }
+/// What `parse_width` accepts, for error messages
+const WIDTH_FORMAT: &str = "a positive number";
 
-        // Group permissions
-        if permissions_mode & 0b000_100_000 == 0b000_100_000 {
-            print!("{read_color}r{reset}");
-        } else {
-            print!("{read_color}-{reset}");
-        }
-        if permissions_mode & 0b000_010_000 == 0b000_010_000 {
-            print!("{write_color}w{reset}");
-        } else {
-            print!("{write_color}-{reset}");
-        }
-        if permissions_mode & 0b000_001_000 == 0b000_001_000 {
-            print!("{execute_color}x{reset}");
-        } else {
-            print!("{execute_color}-{reset}");
-        }
+/// Widest `--gutter` accepted, which keeps the column arithmetic well clear of overflowing
+const MAX_GUTTER: usize = 255;
 
-        // Other permissions
-        if permissions_mode & 0b000_000_100 == 0b000_000_100 {
-            print!("{read_color}r{reset}");
-        } else {
-            print!("{read_color}-{reset}");
-        }
-        if permissions_mode & 0b000_000_010 == 0b000_000_010 {
-            print!("{write_color}w{reset}");
-        } else {
-            print!("{write_color}-{reset}");
-        }
-        if permissions_mode & 0b000_000_001 == 0b000_000_001 {
-            print!("{execute_color}x{reset}");
-        } else {
-            print!("{execute_color}-{reset}");
-        }
+/// What `--gutter` accepts, for error messages
+const GUTTER_FORMAT: &str = "a number from 1 to 255";
+
+/// A grid width or gutter, which has to be a positive number
+fn parse_width(width: &str) -> Option<usize> {
+    width.parse().ok().filter(|&width| width > 0)
+}
+
+fn is_glob(operand: &str) -> bool {
+    operand.contains(['*', '?', '['])
+}
+
+/// Expands a glob against the filesystem one path component at a time, like a shell would.
+/// Hidden names are only matched by a component that starts with a dot itself.
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, MinilsError> {
+    let mut matches = vec![PathBuf::new()];
+
+    for component in Path::new(pattern).components() {
+        let component_pattern = component.as_os_str().to_string_lossy();
 
-        print!("{padding:<2}", padding = "");
+        if !is_glob(&component_pattern) {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        }
 
-        let size = metadata.size();
+        let mut next = Vec::new();
 
-        if !metadata.is_dir() {
-            if size < 10u64.pow(3) {
-                print!("{size:>4}B");
-            } else if size < 10u64.pow(6) {
-                print!("{size:>3}KB", size = size / 10u64.pow(6));
-            } else if size < 10u64.pow(9) {
-                print!("{size:>3}MB", size = size / 10u64.pow(9));
-            } else if size < 10u64.pow(12) {
-                print!("{size:>3}GB", size = size / 10u64.pow(12));
+        for base in &matches {
+            let dir = if base.as_os_str().is_empty() {
+                Path::new(".")
             } else {
-                print!("{size:>3}TB", size = size / 10u64.pow(15));
+                base
+            };
+
+            // Bases that aren't readable directories simply have no matches under them
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let file_name = entry.file_name();
+                let Some(name) = file_name.to_str() else {
+                    continue;
+                };
+
+                if name.starts_with('.') && !component_pattern.starts_with('.') {
+                    continue;
+                }
+
+                if glob_match(&component_pattern, name) {
+                    next.push(base.join(name));
+                }
             }
-        } else {
-            print!("{:>5}", "-");
         }
-        print!("{padding:<2}", padding = "");
-    }
 
-    if display_options.long && entry_type.is_symlink() {
-        let real_path = fs::read_link(entry.path()).unwrap_or_else(err_handling);
-        print!(
-            "{entry_color}{entry_name}{reset} -> {real_path_color}{real_path}{reset}",
-            real_path = real_path.display(),
-            real_path_color = "\x1b[0;31m", // regular red
-        );
-    } else {
-        print!("{entry_color}{entry_name}{padding:<5}{reset}", padding = "");
+        matches = next;
     }
 
-    if display_options.long || display_options.oneline {
-        println!();
+    if matches.is_empty() {
+        return Err(MinilsError::NoMatches(pattern.to_string()));
     }
+
+    Ok(matches)
 }
 
-pub fn run(
-    entries: fs::ReadDir,
-    display_options: DisplayOptions,
-    filtering_options: FilteringOptions,
-) {
-    if display_options.long {
-        println!("\x1b[4mPermissions\x1b[0m  \x1b[4mSize\x1b[0m  \x1b[4mName\x1b[0m");
-    }
+/// Matches a name against a shell-style pattern: `*` matches any run of characters, `?` any
+/// single character, and `[...]` any character in the set, which may contain ranges like `a-z`
+/// and is negated by a leading `!` or `^`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let mut p = 0;
+    let mut n = 0;
+    // The pattern position just after the last `*`, and how far into the name it reaches so far
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            p += 1;
+            star = Some((p, n));
+            continue;
+        }
+
+        if let Some(len) = match_one(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+            continue;
+        }
+
+        // Let the last `*` swallow one more character and try again from there
+        match star {
+            Some((star_p, star_n)) => {
+                p = star_p;
+                n = star_n + 1;
+                star = Some((star_p, n));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Length of the single-character pattern at the start of `pattern` if it matches `c`
+fn match_one(pattern: &[char], c: char) -> Option<usize> {
+    match *pattern.first()? {
+        '?' => Some(1),
+        '[' => {
+            let negated = matches!(pattern.get(1), Some('!' | '^'));
+            let start = if negated { 2 } else { 1 };
+
+            // A `]` right at the start of the set is part of it. Without a closing bracket the
+            // `[` is just an ordinary character.
+            let Some(end) = pattern
+                .iter()
+                .skip(start + 1)
+                .position(|&c| c == ']')
+                .map(|i| i + start + 1)
+            else {
+                return (c == '[').then_some(1);
+            };
+
+            let set = &pattern[start..end];
+            let mut matched = false;
+            let mut i = 0;
+
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    matched |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    matched |= set[i] == c;
+                    i += 1;
+                }
+            }
+
+            (matched != negated).then_some(end + 1)
+        }
+        literal => (literal == c).then_some(1),
+    }
+}
+
+/// `code` when colors are enabled, otherwise nothing
+fn paint(code: &str, use_color: bool) -> &str {
+    if use_color {
+        code
+    } else {
+        ""
+    }
+}
+
+fn write_long_header(
+    columns: &Columns,
+    display_options: &DisplayOptions,
+    use_color: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let underline = paint("\x1b[4m", use_color);
+    let reset = paint("\x1b[0m", use_color);
+
+    let gap = " ".repeat(display_options.gutter);
+
+    if display_options.inode {
+        write!(
+            out,
+            "{:inode_pad$}{underline}Inode{reset}{gap}",
+            "",
+            inode_pad = columns.inode_width - "Inode".len()
+        )?;
+    }
+
+    if display_options.blocks {
+        write!(
+            out,
+            "{:blocks_pad$}{underline}Blocks{reset}{gap}",
+            "",
+            blocks_pad = columns.blocks_width - "Blocks".len()
+        )?;
+    }
+
+    if display_options.octal_permissions {
+        write!(out, "{underline}Mode{reset}{gap}")?;
+    }
+
+    // "Permissions" is one character wider than the permission string itself, which
+    // `Columns::measure` leaves room for, and "Links" and "Size" are right-aligned like the
+    // numbers underneath them
+    write!(
+        out,
+        "{underline}Permissions{reset}{:links_pad$}{underline}Links{reset}{gap}\
+         {underline}User{reset}{:user_pad$}{gap}\
+         {underline}Group{reset}{:group_pad$}{gap}",
+        "",
+        "",
+        "",
+        links_pad = display_options.gutter - 1 + columns.links_width - "Links".len(),
+        user_pad = columns.user_width - "User".len(),
+        group_pad = columns.group_width - "Group".len(),
+    )?;
+
+    if display_options.author {
+        write!(
+            out,
+            "{underline}Author{reset}{:author_pad$}{gap}",
+            "",
+            author_pad = columns.author_width - "Author".len(),
+        )?;
+    }
+
+    write!(
+        out,
+        "{:size_pad$}{underline}Size{reset}{gap}{underline}{time_header}{reset}",
+        "",
+        size_pad = columns.size_width - "Size".len(),
+        time_header = display_options.time_field.header(),
+    )?;
+
+    if display_options.no_filename {
+        return writeln!(out);
+    }
+
+    writeln!(
+        out,
+        "{:time_pad$}{gap}{underline}Name{reset}",
+        "",
+        time_pad = display_options.time_style.width() - display_options.time_field.header().len(),
+    )
+}
+
+/// Built-in colors for common file extensions, used when `LS_COLORS` doesn't say otherwise
+const EXTENSION_COLORS: [(&[&str], &str); 2] = [
+    (
+        &["tar", "gz", "tgz", "bz2", "xz", "zst", "zip", "7z", "rar"],
+        "\x1b[1;31m", // bold red
+    ),
+    (
+        &["png", "jpg", "jpeg", "gif", "bmp", "svg", "webp", "ico"],
+        "\x1b[1;35m", // bold magenta
+    ),
+];
+
+/// Entry colors taken from the `LS_COLORS` environment variable, falling back to the built-in
+/// defaults for anything it doesn't set
+struct LsColors {
+    directory: String,
+    symlink: String,
+    /// Symlinks whose target doesn't exist
+    orphan: String,
+    file: String,
+    executable: String,
+    /// Lowercased name suffixes (usually `.ext`) and their colors, in the order they were given
+    suffixes: Vec<(String, String)>,
+}
+
+impl Default for LsColors {
+    fn default() -> Self {
+        LsColors {
+            directory: String::from("\x1b[1;34m"),  // bold blue
+            symlink: String::from("\x1b[1;96m"),    // bold cyan
+            orphan: String::from("\x1b[1;31;40m"),  // bold red on black
+            file: String::from("\x1b[1m"),          // bold
+            executable: String::from("\x1b[1;32m"), // bold green
+            suffixes: Vec::new(),
+        }
+    }
+}
+
+impl LsColors {
+    fn from_env() -> Self {
+        LsColors::parse(&env::var("LS_COLORS").unwrap_or_default())
+    }
+
+    /// Parses the `di=01;34:ln=01;36:*.tar=01;31` format used by GNU `dircolors`. Keys that
+    /// minils doesn't use and malformed fields are skipped.
+    fn parse(spec: &str) -> Self {
+        let mut ls_colors = LsColors::default();
+
+        for (key, value) in spec.split(':').filter_map(|field| field.split_once('=')) {
+            // `ln=target` means "color links like what they point to", which isn't supported
+            if value.is_empty() || value == "target" {
+                continue;
+            }
+
+            let color = format!("\x1b[{value}m");
+
+            match key {
+                "di" => ls_colors.directory = color,
+                "ln" => ls_colors.symlink = color,
+                "or" => ls_colors.orphan = color,
+                "fi" => ls_colors.file = color,
+                "ex" => ls_colors.executable = color,
+                _ => {
+                    if let Some(suffix) = key.strip_prefix('*') {
+                        ls_colors.suffixes.push((suffix.to_lowercase(), color));
+                    }
+                }
+            }
+        }
+
+        ls_colors
+    }
+
+    /// Color for a listed entry: the orphan color for a broken link, otherwise as in `color_for`
+    fn entry_color(&self, entry: &Entry) -> &str {
+        if entry.is_broken_link() {
+            return &self.orphan;
+        }
+
+        self.color_for(&entry.name, &entry.file_type, entry.mode())
+    }
+
+    /// Color for an entry, chosen by its type and then, for regular files, by whether any execute
+    /// bit is set in `mode`. Other regular files use the last matching `LS_COLORS` suffix, or
+    /// the built-in extension table if none match.
+    fn color_for(&self, name: &str, entry_type: &fs::FileType, mode: u32) -> &str {
+        if entry_type.is_dir() {
+            return &self.directory;
+        }
+        if entry_type.is_symlink() {
+            return &self.symlink;
+        }
+        if entry_type.is_file() && mode & 0o111 != 0 {
+            return &self.executable;
+        }
+
+        let name = name.to_lowercase();
+
+        if let Some((_, color)) = self
+            .suffixes
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+        {
+            return color;
+        }
+
+        EXTENSION_COLORS
+            .iter()
+            .find(|(extensions, _)| extensions.contains(&extension_of(&name)))
+            .map_or(&self.file, |&(_, color)| color)
+    }
+}
+
+/// Nerd Font icons for common file extensions, in the private use area
+const EXTENSION_ICONS: [(&[&str], char); 14] = [
+    (&["rs"], '\u{e7a8}'),
+    (&["py"], '\u{e606}'),
+    (&["js", "mjs"], '\u{e74e}'),
+    (&["ts"], '\u{e628}'),
+    (&["go"], '\u{e626}'),
+    (&["c", "h"], '\u{e61e}'),
+    (&["cpp", "cc", "hpp"], '\u{e61d}'),
+    (&["java"], '\u{e738}'),
+    (&["sh", "bash", "zsh"], '\u{f489}'),
+    (&["md"], '\u{f48a}'),
+    (&["json", "toml", "yaml", "yml"], '\u{e615}'),
+    (&["html", "htm"], '\u{f13b}'),
+    (
+        &["tar", "gz", "tgz", "bz2", "xz", "zst", "zip", "7z", "rar"],
+        '\u{f410}',
+    ),
+    (
+        &["png", "jpg", "jpeg", "gif", "bmp", "svg", "webp", "ico"],
+        '\u{f1c5}',
+    ),
+];
+
+/// Icon for `--icons`: one per file type, and for files one per known extension
+fn icon_for(name: &str, file_type: &fs::FileType) -> char {
+    if file_type.is_dir() {
+        return '\u{f07b}'; // folder
+    }
+    if file_type.is_symlink() {
+        return '\u{f0c1}'; // link
+    }
+    if !file_type.is_file() {
+        return '\u{f013}'; // cog, for devices, sockets and pipes
+    }
+
+    let extension = extension_of(name).to_lowercase();
+    EXTENSION_ICONS
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+        .map_or('\u{f15b}', |&(_, icon)| icon) // plain file
+}
+
+/// An entry's name as listed: quoted as asked, and under `--icons` after its icon
+fn display_name<'a>(entry: &'a Entry, display_options: &DisplayOptions) -> Cow<'a, str> {
+    let name = quote_name(&entry.name, &display_options.quoting_style);
+
+    if display_options.icons {
+        Cow::Owned(format!(
+            "{} {name}",
+            icon_for(&entry.name, &entry.file_type)
+        ))
+    } else {
+        name
+    }
+}
+
+/// Something to list: an entry of a directory that made it past the hidden-file filter, or a
+/// path given on the command line that isn't listed as a directory
+struct Entry {
+    path: PathBuf,
+    name: String,
+    file_type: fs::FileType,
+    /// `None` if the metadata couldn't be read, which has already been reported
+    metadata: Option<fs::Metadata>,
+}
+
+impl Entry {
+    /// Whether this is a symlink whose target can't be resolved. Any error counts, not just a
+    /// missing target.
+    fn is_broken_link(&self) -> bool {
+        self.file_type.is_symlink() && fs::metadata(&self.path).is_err()
+    }
+
+    /// Permission bits, or zero if the metadata couldn't be read
+    fn mode(&self) -> u32 {
+        self.metadata
+            .as_ref()
+            .map_or(0, |metadata| metadata.permissions().mode())
+    }
+}
+
+/// Widths that keep the columns of a listing aligned, along with owner and group names for long
+/// mode, resolved once per ID
+struct Columns {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+    inode_width: usize,
+    blocks_width: usize,
+    links_width: usize,
+    user_width: usize,
+    group_width: usize,
+    /// The owner again, for `--author`
+    author_width: usize,
+    /// Fixed by the choice of units, see `format_size`, unless sizes are printed in full
+    size_width: usize,
+    /// Total size of each directory's contents, for `--du`
+    dir_sizes: HashMap<PathBuf, u64>,
+    /// How many entries each directory holds, for `--dir-counts`
+    dir_counts: HashMap<PathBuf, usize>,
+}
+
+impl Columns {
+    fn measure<'a>(
+        entries: impl Iterator<Item = &'a Entry>,
+        display_options: &DisplayOptions,
+        filtering_options: &FilteringOptions,
+    ) -> Self {
+        let mut columns = Columns {
+            users: HashMap::new(),
+            groups: HashMap::new(),
+            dir_sizes: HashMap::new(),
+            dir_counts: HashMap::new(),
+            // Never narrower than the header, which only long mode has
+            inode_width: if display_options.mode == DisplayMode::Long {
+                "Inode".len()
+            } else {
+                1
+            },
+            blocks_width: if display_options.mode == DisplayMode::Long {
+                "Blocks".len()
+            } else {
+                1
+            },
+            // "Permissions" is one character wider than the permission string, so with a gutter
+            // of 1 the header needs the links column a character wider to keep a space before
+            // "Links"
+            links_width: if display_options.no_header {
+                "Links".len()
+            } else {
+                ("Permissions".len() + 1 + "Links".len())
+                    .saturating_sub("drwxr-xr-x".len() + display_options.gutter)
+                    .max("Links".len())
+            },
+            user_width: "User".len(),
+            group_width: "Group".len(),
+            author_width: "Author".len(),
+            size_width: if display_options.bytes {
+                "Size".len()
+            } else if display_options.human_readable {
+                9
+            } else {
+                5
+            },
+        };
+        let numeric = display_options.numeric_uid_gid;
+
+        for entry in entries {
+            let Some(metadata) = &entry.metadata else {
+                continue;
+            };
+
+            if display_options.inode {
+                columns.inode_width = columns.inode_width.max(metadata.ino().to_string().len());
+            }
+
+            if display_options.blocks {
+                let blocks = blocks(metadata, display_options).to_string();
+                columns.blocks_width = columns.blocks_width.max(blocks.len());
+            }
+
+            if display_options.mode != DisplayMode::Long {
+                continue;
+            }
+
+            columns.links_width = columns.links_width.max(metadata.nlink().to_string().len());
+
+            if display_options.du && metadata.is_dir() {
+                let size = dir_size(&entry.path, display_options, &mut HashSet::new());
+                columns.dir_sizes.insert(entry.path.clone(), size);
+            } else if display_options.dir_counts && metadata.is_dir() {
+                if let Some(count) = dir_count(&entry.path, filtering_options) {
+                    columns.size_width = columns
+                        .size_width
+                        .max(counted(count, "item", "items").len());
+                    columns.dir_counts.insert(entry.path.clone(), count);
+                }
+            }
+
+            if display_options.bytes {
+                if let Some(size) = columns.entry_size(entry, display_options) {
+                    columns.size_width = columns.size_width.max(size.to_string().len());
+                }
+            }
+
+            let user = columns
+                .users
+                .entry(metadata.uid())
+                .or_insert_with_key(|&uid| {
+                    let name = if numeric { None } else { user_name(uid) };
+                    name.unwrap_or_else(|| uid.to_string())
+                });
+            columns.user_width = columns.user_width.max(user.chars().count());
+            columns.author_width = columns.author_width.max(user.chars().count());
+
+            let group = columns
+                .groups
+                .entry(metadata.gid())
+                .or_insert_with_key(|&gid| {
+                    let name = if numeric { None } else { group_name(gid) };
+                    name.unwrap_or_else(|| gid.to_string())
+                });
+            columns.group_width = columns.group_width.max(group.chars().count());
+        }
+
+        columns
+    }
+
+    /// Writes the inode and block count columns that come before everything else, if they were
+    /// asked for, each followed by its gutter
+    fn write_leading(
+        &self,
+        metadata: Option<&fs::Metadata>,
+        display_options: &DisplayOptions,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let inode_width = self.inode_width;
+        let blocks_width = self.blocks_width;
+        let gutter = display_options.gutter;
+
+        if display_options.inode {
+            match metadata {
+                Some(metadata) => write!(out, "{:>inode_width$}{:gutter$}", metadata.ino(), "")?,
+                None => write!(out, "{:>inode_width$}{:gutter$}", "?", "")?,
+            }
+        }
+
+        if display_options.blocks {
+            match metadata {
+                Some(metadata) => write!(
+                    out,
+                    "{:>blocks_width$}{:gutter$}",
+                    blocks(metadata, display_options),
+                    ""
+                )?,
+                None => write!(out, "{:>blocks_width$}{:gutter$}", "?", "")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Width taken up by `write_leading`
+    fn leading_width(&self, display_options: &DisplayOptions) -> usize {
+        let mut width = 0;
+
+        if display_options.inode {
+            width += self.inode_width + display_options.gutter;
+        }
+        if display_options.blocks {
+            width += self.blocks_width + display_options.gutter;
+        }
+
+        width
+    }
+
+    /// A size in the units asked for, padded to the size column. `--bytes` wins over
+    /// `--human-readable`.
+    fn size(&self, size: u64, display_options: &DisplayOptions) -> String {
+        if display_options.bytes {
+            format!("{size:>size_width$}", size_width = self.size_width)
+        } else {
+            // Only wider than the units need when --dir-counts made room for a count
+            format!(
+                "{:>size_width$}",
+                format_size(size, display_options.human_readable),
+                size_width = self.size_width
+            )
+        }
+    }
+
+    /// The size shown for an entry: directories only have one under `--du`
+    fn entry_size(&self, entry: &Entry, display_options: &DisplayOptions) -> Option<u64> {
+        let metadata = entry.metadata.as_ref()?;
+
+        if metadata.is_dir() {
+            self.dir_sizes.get(&entry.path).copied()
+        } else {
+            Some(file_size(metadata, display_options))
+        }
+    }
+
+    fn user(&self, uid: u32) -> &str {
+        self.users.get(&uid).map_or("?", String::as_str)
+    }
+
+    fn group(&self, gid: u32) -> &str {
+        self.groups.get(&gid).map_or("?", String::as_str)
+    }
+}
+
+/// Size of a file in bytes: how long it is, or under `--allocated-size` how much disk space it
+/// takes up, which is less for sparse files and usually more for small ones
+fn file_size(metadata: &fs::Metadata, display_options: &DisplayOptions) -> u64 {
+    if display_options.allocated_size {
+        // st_blocks always counts 512-byte units
+        metadata.blocks() * 512
+    } else {
+        metadata.size()
+    }
+}
+
+/// Total size of the files under `dir`, like `du -s`. Symlinks aren't followed, so there are no
+/// cycles to get stuck in, and files with several hard links count once. Whatever can't be read
+/// is reported and left out.
+fn dir_size(dir: &Path, display_options: &DisplayOptions, seen: &mut HashSet<(u64, u64)>) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            report(format!("reading directory '{}'", dir.display()), err);
+            return 0;
+        }
+    };
+    let mut total = 0;
+
+    for entry in entries {
+        let (path, metadata) = match entry.and_then(|entry| Ok((entry.path(), entry.metadata()?))) {
+            Ok(entry) => entry,
+            Err(err) => {
+                report(format!("reading directory '{}'", dir.display()), err);
+                continue;
+            }
+        };
+
+        if metadata.is_dir() {
+            total += dir_size(&path, display_options, seen);
+        } else if metadata.nlink() == 1 || seen.insert((metadata.dev(), metadata.ino())) {
+            total += file_size(&metadata, display_options);
+        }
+    }
+
+    total
+}
+
+/// Number of entries directly inside `dir` that a listing of it would show, without `.` and
+/// `..` and leaving hidden ones out unless `-a` or `-A` were given. `None` if it can't be read,
+/// which is reported.
+fn dir_count(dir: &Path, filtering_options: &FilteringOptions) -> Option<usize> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            report(format!("reading directory '{}'", dir.display()), err);
+            return None;
+        }
+    };
+    let show_hidden = filtering_options.all || filtering_options.almost_all;
+
+    Some(
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| show_hidden || !entry.file_name().as_bytes().starts_with(b"."))
+            .count(),
+    )
+}
+
+/// What a listing showed, for the `--total` footer
+#[derive(Default)]
+struct Summary {
+    files: usize,
+    dirs: usize,
+    /// Combined size of everything but directories
+    size: u64,
+    /// Directories that couldn't be opened, which have already been reported
+    unopened: usize,
+    /// Directories left out because listing them would go round in a cycle, which have already
+    /// been reported
+    cycles: usize,
+}
+
+impl Summary {
+    fn count(&mut self, metadata: &fs::Metadata) {
+        if metadata.is_dir() {
+            self.dirs += 1;
+        } else {
+            self.files += 1;
+            self.size += metadata.size();
+        }
+    }
+
+    /// Writes a line like `3 files, 2 directories, 4KB total`
+    fn write(&self, display_options: &DisplayOptions, out: &mut impl Write) -> io::Result<()> {
+        let size = format_total(self.size, display_options);
+
+        writeln!(
+            out,
+            "{}, {}, {size} total",
+            counted(self.files, "file", "files"),
+            counted(self.dirs, "directory", "directories"),
+        )
+    }
+}
+
+/// A count followed by the noun it counts, like `1 file` or `2 files`
+fn counted(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("{count} {singular}")
+    } else {
+        format!("{count} {plural}")
+    }
+}
+
+/// A size outside of the size column, so without its padding
+fn format_total(size: u64, display_options: &DisplayOptions) -> String {
+    if display_options.bytes {
+        format!("{size}B")
+    } else {
+        format_size(size, display_options.human_readable)
+            .trim_start()
+            .to_string()
+    }
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, other: Summary) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.size += other.size;
+        self.unopened += other.unopened;
+        self.cycles += other.cycles;
+    }
+}
+
+/// Characters that would make a shell split or expand a name
+const SHELL_SPECIAL: &str = " \t'\"\\$`!*?[]{}()<>|&;#~";
+
+/// `name` as it should be printed under `style`
+fn quote_name<'a>(name: &'a str, style: &QuotingStyle) -> Cow<'a, str> {
+    match style {
+        QuotingStyle::Auto | QuotingStyle::Literal => Cow::Borrowed(name),
+        QuotingStyle::Shell => {
+            if !name
+                .chars()
+                .any(|c| c.is_control() || SHELL_SPECIAL.contains(c))
+            {
+                return Cow::Borrowed(name);
+            }
+
+            let mut quoted = String::from("'");
+            for c in name.chars() {
+                match c {
+                    // Close the quotes, add an escaped quote and open them again
+                    '\'' => quoted.push_str("'\\''"),
+                    c if c.is_control() => quoted.push('?'),
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('\'');
+
+            Cow::Owned(quoted)
+        }
+        QuotingStyle::Escape => {
+            if !name
+                .chars()
+                .any(|c| c.is_control() || c == ' ' || c == '\\')
+            {
+                return Cow::Borrowed(name);
+            }
+
+            let mut escaped = String::new();
+            for c in name.chars() {
+                match c {
+                    ' ' => escaped.push_str("\\ "),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\t' => escaped.push_str("\\t"),
+                    '\r' => escaped.push_str("\\r"),
+                    c if c.is_control() => escaped.push_str(&format!("\\{:03o}", c as u32)),
+                    c => escaped.push(c),
+                }
+            }
+
+            Cow::Owned(escaped)
+        }
+    }
+}
+
+/// Type indicator appended to names in the given style
+fn indicator(entry_type: &fs::FileType, mode: u32, style: &IndicatorStyle) -> &'static str {
+    if *style == IndicatorStyle::None {
+        ""
+    } else if entry_type.is_dir() {
+        "/"
+    } else if *style == IndicatorStyle::Slash {
+        ""
+    } else if entry_type.is_symlink() {
+        "@"
+    } else if entry_type.is_fifo() {
+        "|"
+    } else if entry_type.is_socket() {
+        "="
+    } else if mode & 0o111 != 0 {
+        "*"
+    } else {
+        ""
+    }
+}
+
+/// Writes the nine `rwx` permission characters of `mode`, with the owner's in bold
+fn write_permissions(mode: u32, use_color: bool, out: &mut impl Write) -> io::Result<()> {
+    let reset = paint("\x1b[0m", use_color);
+    let read_color = paint("\x1b[33m", use_color); // yellow
+    let write_color = paint("\x1b[31m", use_color); // red
+    let execute_color = paint("\x1b[32m", use_color); // green
+    let is_user = paint("\x1b[1m", use_color); // bold
+
+    let permissions = [('r', read_color), ('w', write_color), ('x', execute_color)];
+
+    for (i, &(permission, color)) in permissions.iter().cycle().take(9).enumerate() {
+        let bit = 0b100_000_000 >> i;
+        let weight = if i < 3 { is_user } else { "" };
+
+        if mode & bit == bit {
+            write!(out, "{weight}{color}{permission}{reset}")?;
+        } else {
+            write!(out, "{weight}{color}-{reset}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Color for a size under `--color-scale`, one step brighter for every factor of 1024
+fn size_color(size: u64) -> &'static str {
+    // Powers of 1024 are every 10 bits
+    match size.checked_ilog2().unwrap_or(0) / 10 {
+        0 => "\x1b[2m",       // faint, under 1KiB
+        1 => "\x1b[0;32m",    // regular green, under 1MiB
+        2 => "\x1b[0;33m",    // regular yellow, under 1GiB
+        3 => "\x1b[1;31m",    // bold red, under 1TiB
+        _ => "\x1b[1;37;41m", // bold white on red
+    }
+}
+
+/// Writes the permission bits of `mode`, including setuid, setgid and sticky, as four octal
+/// digits
+fn write_octal_permissions(mode: u32, out: &mut impl Write) -> io::Result<()> {
+    write!(out, "{:04o}", mode & 0o7777)
+}
+
+fn print_entry(
+    entry: &Entry,
+    display_options: &DisplayOptions,
+    columns: &Columns,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let entry_name = display_name(entry, display_options);
+    let entry_type = &entry.file_type;
+    let mode = entry.mode();
+    let entry_color = paint(ls_colors.entry_color(entry), use_color);
+    let reset = paint("\x1b[0m", use_color);
+    let target = link_target(entry, display_options);
+    let indicator = if target.is_some() {
+        ""
+    } else {
+        indicator(entry_type, mode, &display_options.indicator_style)
+    };
+
+    columns.write_leading(entry.metadata.as_ref(), display_options, out)?;
+
+    if display_options.mode == DisplayMode::Long {
+        let links_width = columns.links_width;
+        let user_width = columns.user_width;
+        let group_width = columns.group_width;
+        let author_width = columns.author_width;
+        let size_width = columns.size_width;
+        let time_width = display_options.time_style.width();
+        let gutter = display_options.gutter;
+
+        if display_options.octal_permissions {
+            match &entry.metadata {
+                Some(_) => write_octal_permissions(mode, out)?,
+                None => write!(out, "????")?,
+            }
+            write!(out, "{padding:<gutter$}", padding = "")?;
+        }
+
+        if entry_type.is_dir() {
+            write!(out, "{entry_color}d{reset}")?;
+        } else if entry_type.is_file() {
+            write!(out, "{entry_color}-{reset}")?;
+        } else {
+            write!(out, "{entry_color}l{reset}")?;
+        }
+
+        match &entry.metadata {
+            Some(metadata) => {
+                write_permissions(mode, use_color, out)?;
+                write!(out, "{padding:<gutter$}", padding = "")?;
+
+                write!(
+                    out,
+                    "{:>links_width$}{padding:<gutter$}{:<user_width$}{padding:<gutter$}\
+                     {:<group_width$}{padding:<gutter$}",
+                    metadata.nlink(),
+                    columns.user(metadata.uid()),
+                    columns.group(metadata.gid()),
+                    padding = ""
+                )?;
+
+                // On Unix the author of a file is always its owner
+                if display_options.author {
+                    write!(
+                        out,
+                        "{:<author_width$}{padding:<gutter$}",
+                        columns.user(metadata.uid()),
+                        padding = ""
+                    )?;
+                }
+
+                match columns.entry_size(entry, display_options) {
+                    Some(size) => {
+                        let size_color = if display_options.color_scale {
+                            paint(size_color(size), use_color)
+                        } else {
+                            ""
+                        };
+                        let reset = paint("\x1b[0m", use_color);
+                        write!(
+                            out,
+                            "{size_color}{}{reset}",
+                            columns.size(size, display_options)
+                        )?
+                    }
+                    None => match columns.dir_counts.get(&entry.path) {
+                        Some(&count) => {
+                            write!(out, "{:>size_width$}", counted(count, "item", "items"))?
+                        }
+                        None => write!(out, "{:>size_width$}", "-")?,
+                    },
+                }
+                write!(out, "{padding:<gutter$}", padding = "")?;
+
+                match display_options.time_field.of(metadata) {
+                    Some(time) => write!(
+                        out,
+                        "{}",
+                        format_time(time, SystemTime::now(), &display_options.time_style)
+                    )?,
+                    None => write!(out, "{:>time_width$}", "?")?,
+                }
+            }
+            None => {
+                write!(
+                    out,
+                    "?????????{padding:<gutter$}{:>links_width$}{padding:<gutter$}{:<user_width$}\
+                     {padding:<gutter$}{:<group_width$}{padding:<gutter$}",
+                    "?",
+                    "?",
+                    "?",
+                    padding = ""
+                )?;
+
+                if display_options.author {
+                    write!(
+                        out,
+                        "{:<author_width$}{padding:<gutter$}",
+                        "?",
+                        padding = ""
+                    )?;
+                }
+
+                write!(
+                    out,
+                    "{:>size_width$}{padding:<gutter$}{:>time_width$}",
+                    "?",
+                    "?",
+                    padding = ""
+                )?;
+            }
+        }
+
+        // Which leaves the time as the last column
+        if display_options.no_filename {
+            return writeln!(out);
+        }
+        write!(out, "{padding:<gutter$}", padding = "")?;
+    }
+
+    // Names are the last column, so there's nothing to pad them out for
+    let (link_start, link_end) = hyperlink(entry, display_options, use_color);
+    write!(
+        out,
+        "{link_start}{entry_color}{entry_name}{indicator}{reset}{link_end}"
+    )?;
+    if let Some(target) = &target {
+        write_link_target(entry, target, entry_color, use_color, out)?;
+    }
+
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// The OSC 8 escape sequences that make a name a link to its file under `--hyperlink`, to go
+/// before and after it. Empty, like colors, when escape codes are off.
+fn hyperlink(
+    entry: &Entry,
+    display_options: &DisplayOptions,
+    use_color: bool,
+) -> (String, &'static str) {
+    if !display_options.hyperlink || !use_color {
+        return (String::new(), "");
+    }
+
+    // Made absolute without resolving symlinks, so the link goes where the listing says
+    let Ok(path) = std::path::absolute(&entry.path) else {
+        return (String::new(), "");
+    };
+
+    // Everything that isn't safe in a URI path is percent-encoded, byte by byte
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    (format!("\x1b]8;;{uri}\x1b\\"), "\x1b]8;;\x1b\\")
+}
+
+/// What a symlink points to, quoted like a name, when long mode or `--link-target` show it.
+/// Dangling links still have a target to show; one that can't be read at all shows as `?`.
+fn link_target(entry: &Entry, display_options: &DisplayOptions) -> Option<String> {
+    if !entry.file_type.is_symlink()
+        || display_options.mode != DisplayMode::Long && !display_options.link_target
+    {
+        return None;
+    }
+
+    let target = fs::read_link(&entry.path).unwrap_or_else(|err| {
+        report(format!("reading link '{}'", entry.path.display()), err);
+        PathBuf::from("?")
+    });
+
+    Some(quote_name(&target.to_string_lossy(), &display_options.quoting_style).into_owned())
+}
+
+/// Width of the ` -> target` that follows a symlink's name
+fn link_target_width(target: Option<&String>) -> usize {
+    target.map_or(0, |target| " -> ".len() + target.chars().count())
+}
+
+/// Writes ` -> target` after a symlink's name, in red unless the link is broken, in which case
+/// it takes the link's own color
+fn write_link_target(
+    entry: &Entry,
+    target: &str,
+    entry_color: &str,
+    use_color: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let target_color = if entry.is_broken_link() {
+        entry_color
+    } else {
+        paint("\x1b[0;31m", use_color) // regular red
+    };
+    let reset = paint("\x1b[0m", use_color);
+
+    write!(out, " -> {target_color}{target}{reset}")
+}
+
+/// Number of grid columns that fit in `width` when every column is as wide as the longest name
+/// plus the gutter. Always at least one, and never more than there are names.
+fn grid_columns(name_widths: &[usize], width: usize, gutter: usize) -> usize {
+    let column_width = name_widths.iter().max().copied().unwrap_or(0) + gutter;
+
+    (width.saturating_add(gutter) / column_width).clamp(1, name_widths.len().max(1))
+}
+
+/// Prints entries in aligned columns filled top to bottom, like `ls`, or left to right under
+/// `-x`. Without a known terminal width everything goes in a single column.
+fn print_grid(
+    entries: &[&Entry],
+    width: Option<usize>,
+    display_options: &DisplayOptions,
+    columns: &Columns,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let reset = paint("\x1b[0m", use_color);
+    let targets: Vec<Option<String>> = entries
+        .iter()
+        .map(|entry| link_target(entry, display_options))
+        .collect();
+    let indicators: Vec<&str> = entries
+        .iter()
+        .zip(&targets)
+        .map(|(entry, target)| {
+            if target.is_some() {
+                return "";
+            }
+            indicator(
+                &entry.file_type,
+                entry.mode(),
+                &display_options.indicator_style,
+            )
+        })
+        .collect();
+    let names: Vec<Cow<str>> = entries
+        .iter()
+        .map(|entry| display_name(entry, display_options))
+        .collect();
+    let name_widths: Vec<usize> = names
+        .iter()
+        .zip(&indicators)
+        .zip(&targets)
+        .map(|((name, indicator), target)| {
+            name.chars().count() + indicator.len() + link_target_width(target.as_ref())
+        })
+        .map(|name_width| name_width + columns.leading_width(display_options))
+        .collect();
+    let column_width = name_widths.iter().max().copied().unwrap_or(0) + display_options.gutter;
+
+    let column_count = match width {
+        Some(width) => grid_columns(&name_widths, width, display_options.gutter),
+        None => 1,
+    };
+    let rows = entries.len().div_ceil(column_count);
+
+    for row in 0..rows {
+        // Indices of the entries on this row, left to right
+        let cells: Vec<usize> = if display_options.mode == DisplayMode::Across {
+            (row * column_count..entries.len().min((row + 1) * column_count)).collect()
+        } else {
+            (row..entries.len()).step_by(rows).collect()
+        };
+
+        for (cell, &i) in cells.iter().enumerate() {
+            let entry = entries[i];
+            let entry_name = &names[i];
+            let entry_color = paint(ls_colors.entry_color(entry), use_color);
+            let indicator = indicators[i];
+            let (link_start, link_end) = hyperlink(entry, display_options, use_color);
+            columns.write_leading(entry.metadata.as_ref(), display_options, out)?;
+            write!(
+                out,
+                "{link_start}{entry_color}{entry_name}{indicator}{reset}{link_end}"
+            )?;
+            if let Some(target) = &targets[i] {
+                write_link_target(entry, target, entry_color, use_color, out)?;
+            }
+
+            // No trailing padding after the last name on a row
+            if cell + 1 < cells.len() {
+                write!(out, "{:1$}", "", column_width - name_widths[i])?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Prints entries as `a, b, c`, like `ls -m`. A line breaks after the comma whenever the next
+/// name wouldn't fit in `width`.
+fn print_commas(
+    entries: &[&Entry],
+    width: usize,
+    display_options: &DisplayOptions,
+    columns: &Columns,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let reset = paint("\x1b[0m", use_color);
+    let mut line_width = 0;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let entry_name = display_name(entry, display_options);
+        let target = link_target(entry, display_options);
+        let indicator = if target.is_some() {
+            ""
+        } else {
+            indicator(
+                &entry.file_type,
+                entry.mode(),
+                &display_options.indicator_style,
+            )
+        };
+        let name_width = columns.leading_width(display_options)
+            + entry_name.chars().count()
+            + indicator.len()
+            + link_target_width(target.as_ref());
+
+        if i > 0 {
+            // Room for the ", " before the name and the "," after it
+            if line_width + 2 + name_width + 1 > width {
+                writeln!(out, ",")?;
+                line_width = 0;
+            } else {
+                write!(out, ", ")?;
+                line_width += 2;
+            }
+        }
+
+        let entry_color = paint(ls_colors.entry_color(entry), use_color);
+        let (link_start, link_end) = hyperlink(entry, display_options, use_color);
+        columns.write_leading(entry.metadata.as_ref(), display_options, out)?;
+        write!(
+            out,
+            "{link_start}{entry_color}{entry_name}{indicator}{reset}{link_end}"
+        )?;
+        if let Some(target) = &target {
+            write_link_target(entry, target, entry_color, use_color, out)?;
+        }
+        line_width += name_width;
+    }
+
+    if !entries.is_empty() {
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Orders names case-insensitively, falling back to a byte-wise comparison so that names
+/// differing only in case still sort deterministically. `--case-sensitive` skips straight to
+/// the byte-wise comparison, which puts every uppercase ASCII letter before the lowercase ones.
+fn compare_names(a: &str, b: &str, case_sensitive: bool) -> Ordering {
+    if case_sensitive {
+        return a.cmp(b);
+    }
+
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| a.cmp(b))
+}
+
+/// Orders names naturally, so that `file2` comes before `file10`: runs of digits compare by
+/// their value and the text between them like `compare_names`.
+fn compare_versions(a: &str, b: &str, case_sensitive: bool) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a, b);
+
+    while !a_rest.is_empty() && !b_rest.is_empty() {
+        let (a_chunk, a_tail) = split_chunk(a_rest);
+        let (b_chunk, b_tail) = split_chunk(b_rest);
+        let a_numeric = a_chunk.starts_with(|c: char| c.is_ascii_digit());
+        let b_numeric = b_chunk.starts_with(|c: char| c.is_ascii_digit());
+
+        let ordering = if a_numeric && b_numeric {
+            // Compared as strings so that arbitrarily long numbers can't overflow
+            let a_digits = a_chunk.trim_start_matches('0');
+            let b_digits = b_chunk.trim_start_matches('0');
+            a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits))
+        } else if case_sensitive {
+            a_chunk.cmp(b_chunk)
+        } else {
+            a_chunk.to_lowercase().cmp(&b_chunk.to_lowercase())
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        (a_rest, b_rest) = (a_tail, b_tail);
+    }
+
+    // A name that runs out first is a prefix of the other one, as far as versions go
+    (!a_rest.is_empty())
+        .cmp(&!b_rest.is_empty())
+        .then_with(|| compare_names(a, b, case_sensitive))
+}
+
+/// Splits off the leading run of digits or of non-digits
+fn split_chunk(name: &str) -> (&str, &str) {
+    let numeric = name.starts_with(|c: char| c.is_ascii_digit());
+    let end = name
+        .find(|c: char| c.is_ascii_digit() != numeric)
+        .unwrap_or(name.len());
+
+    name.split_at(end)
+}
+
+/// The chosen time of an entry, or `None` if the platform or filesystem can't provide one.
+fn entry_time(entry: &Entry, time_field: &TimeField) -> Option<SystemTime> {
+    time_field.of(entry.metadata.as_ref()?)
+}
+
+/// Size of an entry for sorting purposes. Directories count as empty, so they cluster at the
+/// bottom of a size-sorted listing rather than being ranked by their allocation size.
+fn sort_size(entry: &Entry) -> u64 {
+    if entry.file_type.is_dir() {
+        return 0;
+    }
+
+    entry
+        .metadata
+        .as_ref()
+        .map_or(0, |metadata| metadata.size())
+}
+
+/// Text after the last '.' in a name, or an empty string if there is none. A leading dot marks
+/// a hidden file rather than an extension, so `.gitignore` has no extension.
+fn extension_of(name: &str) -> &str {
+    match name.rfind('.') {
+        None | Some(0) => "",
+        Some(dot) => &name[dot + 1..],
+    }
+}
+
+/// Sorts by the keys asked for, in order, each later key only breaking ties of the earlier
+/// ones. Whatever the keys, remaining ties go to `compare_names` and then to the raw path
+/// bytes, which only differ when names that aren't valid UTF-8 were decoded alike, so the order
+/// never depends on the one the filesystem happened to return.
+fn sort_entries(entries: &mut [Entry], display_options: &DisplayOptions) {
+    let case_sensitive = display_options.case_sensitive;
+    let time_field = &display_options.time_field;
+
+    let by_key = |a: &Entry, b: &Entry, key: &SortKey| match key {
+        SortKey::Name => compare_names(&a.name, &b.name, case_sensitive),
+        // Newest first; entries without a readable timestamp end up last
+        SortKey::Time => entry_time(b, time_field).cmp(&entry_time(a, time_field)),
+        // Largest first
+        SortKey::Size => sort_size(b).cmp(&sort_size(a)),
+        // Names without an extension compare as "" and so come first
+        SortKey::Extension => {
+            compare_names(extension_of(&a.name), extension_of(&b.name), case_sensitive)
+        }
+        SortKey::Version => compare_versions(&a.name, &b.name, case_sensitive),
+        // Symlinks to directories are not dereferenced and stay with the files
+        SortKey::Type => b.file_type.is_dir().cmp(&a.file_type.is_dir()),
+    };
+
+    if display_options.sort_keys.is_empty() {
+        return;
+    }
+
+    entries.sort_by(|a, b| {
+        display_options
+            .sort_keys
+            .iter()
+            .fold(Ordering::Equal, |ordering, key| {
+                ordering.then_with(|| by_key(a, b, key))
+            })
+            .then_with(|| compare_names(&a.name, &b.name, case_sensitive))
+            .then_with(|| a.path.as_os_str().cmp(b.path.as_os_str()))
+    });
+}
+
+/// Lists the operands, returning a failing exit code if some of them couldn't be listed. Those
+/// have already been reported, and everything else is still listed.
+pub fn run(
+    operands: Vec<PathBuf>,
+    mut display_options: DisplayOptions,
+    filtering_options: FilteringOptions,
+) -> Result<ExitCode, MinilsError> {
+    if display_options.quoting_style == QuotingStyle::Auto {
+        display_options.quoting_style = if io::stdout().is_terminal() {
+            QuotingStyle::Shell
+        } else {
+            QuotingStyle::Literal
+        };
+    }
+
+    let use_color = match display_options.color {
+        // Escape codes would only corrupt the output for whatever is parsing it
+        _ if display_options.json || display_options.zero || display_options.raw => false,
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        // NO_COLOR (https://no-color.org) only applies when colors weren't asked for explicitly
+        ColorMode::Auto => {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    };
+    let ls_colors = LsColors::from_env();
+
+    if display_options.pager && io::stdout().is_terminal() {
+        let pager = env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.is_empty())
+            .unwrap_or_else(|| "less".to_string());
+
+        return run_paged(
+            operands,
+            &display_options,
+            &filtering_options,
+            use_color,
+            &ls_colors,
+            &pager,
+        );
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    // Flush whatever was listed before reporting an error
+    let result = list_operands(
+        operands,
+        &display_options,
+        &filtering_options,
+        use_color,
+        &ls_colors,
+        &mut out,
+    );
+    out.flush()?;
+
+    result
+}
+
+/// Like `run`, but writes the listing to the `pager` shell command: `$PAGER`, or `less` if that
+/// isn't set. The pager shares the terminal, so everything that depends on stdout being one
+/// still applies.
+fn run_paged(
+    operands: Vec<PathBuf>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    pager: &str,
+) -> Result<ExitCode, MinilsError> {
+    // Through a shell, since $PAGER often carries its own options. Like git, less is told to pass
+    // colors through and to quit straight away if everything fits on one screen, unless $LESS
+    // says otherwise.
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(pager).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut pager = command
+        .spawn()
+        .map_err(|err| MinilsError::Failed(format!("starting pager '{pager}'"), err))?;
+
+    let mut out = BufWriter::new(pager.stdin.take().expect("the pager's stdin is piped"));
+    let result = list_operands(
+        operands,
+        display_options,
+        filtering_options,
+        use_color,
+        ls_colors,
+        &mut out,
+    );
+    let flushed = out.flush();
+    // Closing its stdin is what tells the pager the listing is over
+    drop(out);
+    pager
+        .wait()
+        .map_err(|err| MinilsError::Failed("waiting for the pager".to_string(), err))?;
+
+    // Quitting the pager early closes the pipe, which isn't an error on our side
+    match (result, flushed) {
+        (Err(MinilsError::Io(err)), _) | (Ok(_), Err(err))
+            if err.kind() == io::ErrorKind::BrokenPipe =>
+        {
+            Ok(ExitCode::SUCCESS)
+        }
+        (result, flushed) => {
+            flushed?;
+            result
+        }
+    }
+}
+
+/// Lists every path given on the command line: anything that isn't a directory to descend into
+/// comes first, followed by one block per directory. Operands that can't be accessed are
+/// reported up front and skipped.
+fn list_operands(
+    operands: Vec<PathBuf>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> Result<ExitCode, MinilsError> {
+    let multiple_operands = operands.len() > 1;
+    let mut files: Vec<Entry> = Vec::new();
+    let mut dirs: Vec<Entry> = Vec::new();
+    let mut status = ExitCode::SUCCESS;
+
+    let follow_links = display_options.dereference || display_options.dereference_command_line;
+
+    for operand in operands {
+        let metadata = if follow_links {
+            fs::metadata(&operand)
+        } else {
+            fs::symlink_metadata(&operand)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                eprintln!("minils: {}", MinilsError::CannotAccess(operand, err));
+                // Like ls, a missing operand is as serious as an error that stops the listing
+                status = ExitCode::from(2);
+                continue;
+            }
+        };
+
+        // Like ls, a link to a directory is still listed as that directory, unless it's the link
+        // itself that long mode or --classify were asked to describe
+        let is_dir = metadata.is_dir()
+            || metadata.is_symlink()
+                && display_options.mode != DisplayMode::Long
+                && display_options.indicator_style != IndicatorStyle::Classify
+                && fs::metadata(&operand).is_ok_and(|target| target.is_dir());
+
+        if is_dir && !filtering_options.list_dirs {
+            // Sorted by the directory itself, whatever link led to it
+            let metadata = fs::metadata(&operand).unwrap_or(metadata);
+            dirs.push(Entry {
+                name: operand.display().to_string(),
+                path: operand,
+                file_type: metadata.file_type(),
+                metadata: Some(metadata),
+            });
+        } else {
+            // Rendered just like a directory entry, named by the path that was given
+            files.push(Entry {
+                name: operand.display().to_string(),
+                path: operand,
+                file_type: metadata.file_type(),
+                metadata: Some(metadata),
+            });
+        }
+    }
+
+    sort_entries(&mut files, display_options);
+    sort_entries(&mut dirs, display_options);
+
+    // Like ls, there's no order to reverse without sorting
+    if display_options.reverse && !display_options.sort_keys.is_empty() {
+        files.reverse();
+        dirs.reverse();
+    }
+
+    let dirs: Vec<PathBuf> = dirs.into_iter().map(|dir| dir.path).collect();
+
+    let mut summary = Summary::default();
+
+    if display_options.json
+        || display_options.zero
+        || display_options.raw
+        || display_options.summary
+    {
+        let terminator: &[u8] = if display_options.zero { b"\0" } else { b"\n" };
+
+        if can_stream(display_options) {
+            write_bare_names(&files, terminator, out)?;
+            for dir in &dirs {
+                stream_entries(
+                    dir,
+                    display_options,
+                    filtering_options,
+                    &mut summary,
+                    |entry| write_bare_names(slice::from_ref(entry), terminator, out),
+                )?;
+            }
+
+            return Ok(unopened_status(status, &summary));
+        }
+
+        let entries = flat_entries(
+            files,
+            &dirs,
+            display_options,
+            filtering_options,
+            &mut summary,
+        );
+
+        if display_options.json {
+            write_json(&entries, out)?;
+        } else if display_options.summary {
+            write_report(&entries, display_options, out)?;
+        } else {
+            write_bare_names(&entries, terminator, out)?;
+        }
+
+        return Ok(unopened_status(status, &summary));
+    }
+
+    if display_options.count_only {
+        let mut count = files.len();
+
+        for dir in &dirs {
+            let identity = fs::metadata(dir)
+                .ok()
+                .map(|metadata| dir_identity(&metadata));
+            count += count_entries(
+                dir,
+                &mut vec![identity],
+                display_options,
+                filtering_options,
+                &mut summary,
+            );
+        }
+
+        writeln!(out, "{count}")?;
+        return Ok(unopened_status(status, &summary));
+    }
+
+    if let Some(pattern) = &filtering_options.find {
+        for entry in files.iter().filter(|entry| {
+            let name = entry.path.file_name().unwrap_or(entry.path.as_os_str());
+            glob_match(pattern, &name.to_string_lossy())
+                && matches_filters(entry, filtering_options)
+        }) {
+            write_found(entry, display_options, use_color, ls_colors, out)?;
+        }
+
+        for dir in &dirs {
+            let identity = fs::metadata(dir)
+                .ok()
+                .map(|metadata| dir_identity(&metadata));
+            summary += find_entries(
+                dir,
+                &mut vec![identity],
+                display_options,
+                filtering_options,
+                use_color,
+                ls_colors,
+                out,
+            )?;
+        }
+
+        return Ok(unopened_status(status, &summary));
+    }
+
+    let columns = Columns::measure(files.iter(), display_options, filtering_options);
+
+    // One header for the whole block of files
+    if display_options.mode == DisplayMode::Long && !display_options.no_header && !files.is_empty()
+    {
+        write_long_header(&columns, display_options, use_color, out)?;
+    }
+
+    for entry in &files {
+        if let Some(metadata) = &entry.metadata {
+            summary.count(metadata);
+        }
+        print_entry(entry, display_options, &columns, use_color, ls_colors, out)?;
+    }
+
+    for (i, dir) in dirs.iter().enumerate() {
+        if i > 0 || !files.is_empty() {
+            writeln!(out)?;
+        }
+
+        let identity = fs::metadata(dir)
+            .ok()
+            .map(|metadata| dir_identity(&metadata));
+
+        // The root of a tree names the directory already
+        if display_options.tree {
+            writeln!(out, "{}", dir.display())?;
+            summary += print_tree(
+                dir,
+                &mut vec![(true, identity)],
+                display_options,
+                filtering_options,
+                use_color,
+                ls_colors,
+                out,
+            )?;
+            continue;
+        }
+
+        if multiple_operands {
+            writeln!(out, "{}:", dir.display())?;
+        }
+
+        summary += list_entries(
+            dir,
+            &mut vec![identity],
+            display_options,
+            filtering_options,
+            use_color,
+            ls_colors,
+            out,
+        )?;
+    }
+
+    if display_options.total {
+        summary.write(display_options, out)?;
+    }
+
+    Ok(unopened_status(status, &summary))
+}
+
+/// `status`, unless a directory couldn't be opened or was left out as a cycle, which like in ls
+/// is as serious as a missing operand
+fn unopened_status(status: ExitCode, summary: &Summary) -> ExitCode {
+    if summary.unopened > 0 || summary.cycles > 0 {
+        ExitCode::from(2)
+    } else {
+        status
+    }
+}
+
+/// The entries of `dir` that pass the hidden-file and ignore rules, read one at a time in
+/// whatever order the filesystem returns them
+fn unsorted_entries<'a>(
+    dir: &'a Path,
+    display_options: &'a DisplayOptions,
+    filtering_options: &'a FilteringOptions,
+) -> Result<impl Iterator<Item = Entry> + 'a, MinilsError> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| MinilsError::CannotOpenDir(dir.to_path_buf(), err))?;
+
+    Ok(entries.filter_map(move |entry| read_entry(entry, dir, display_options, filtering_options)))
+}
+
+/// An entry read from `dir`, or `None` if it's hidden, ignored or couldn't be read, which is
+/// reported
+fn read_entry(
+    entry: io::Result<fs::DirEntry>,
+    dir: &Path,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+) -> Option<Entry> {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(err) => {
+            report(format!("reading directory '{}'", dir.display()), err);
+            return None;
+        }
+    };
+
+    let entry_type = match entry.file_type() {
+        Ok(entry_type) => entry_type,
+        Err(err) => {
+            report(
+                format!("reading the type of '{}'", entry.path().display()),
+                err,
+            );
+            return None;
+        }
+    };
+
+    // Names that aren't valid UTF-8 are shown with U+FFFD in place of the bad bytes rather than
+    // hiding the rest of the directory
+    let entry_name = entry.file_name().to_string_lossy().into_owned();
+
+    if !filtering_options.all && !filtering_options.almost_all && entry_name.starts_with('.') {
+        return None;
+    }
+
+    if filtering_options
+        .ignore
+        .iter()
+        .any(|pattern| glob_match(pattern, &entry_name))
+    {
+        return None;
+    }
+
+    // A single unreadable entry shouldn't hide the rest of the listing
+    let mut metadata = match entry.metadata() {
+        Ok(metadata) => Some(metadata),
+        Err(err) => {
+            report(
+                format!("reading metadata for '{}'", entry.path().display()),
+                err,
+            );
+            None
+        }
+    };
+    let mut entry_type = entry_type;
+
+    // From here on the entry looks like whatever the link points to. Broken links keep showing
+    // up as links.
+    if display_options.dereference && entry_type.is_symlink() {
+        match fs::metadata(entry.path()) {
+            Ok(target_metadata) => {
+                entry_type = target_metadata.file_type();
+                metadata = Some(target_metadata);
+            }
+            Err(err) => report(format!("following link '{}'", entry.path().display()), err),
+        }
+    }
+
+    Some(Entry {
+        path: entry.path(),
+        name: entry_name,
+        file_type: entry_type,
+        metadata,
+    })
+}
+
+/// Reads the entries of `dir` that pass the hidden-file and ignore rules, in display order
+fn read_entries(
+    dir: &Path,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+) -> Result<Vec<Entry>, MinilsError> {
+    let mut visible: Vec<Entry> =
+        unsorted_entries(dir, display_options, filtering_options)?.collect();
+
+    sort_entries(&mut visible, display_options);
+
+    if display_options.reverse && !display_options.sort_keys.is_empty() {
+        visible.reverse();
+    }
+
+    // Stable, so each group keeps the order established above, --reverse included: grouping
+    // comes after reversing so the groups themselves never swap. Symlinks to directories are not
+    // dereferenced and stay with the files.
+    if display_options.group_dirs_first {
+        visible.sort_by_key(|entry| !entry.file_type.is_dir());
+    } else if display_options.group_dirs_last {
+        visible.sort_by_key(|entry| entry.file_type.is_dir());
+    }
+
+    Ok(visible)
+}
+
+/// Whether entries can be written out as they're read rather than collected first, which keeps
+/// `-U` quick on huge directories: only when nothing sorts or groups them, and the output doesn't
+/// need to see every entry before laying out the first one
+fn can_stream(display_options: &DisplayOptions) -> bool {
+    let bare_names = (display_options.raw || display_options.zero)
+        && !display_options.json
+        && !display_options.summary;
+    let plain_lines = display_options.mode == DisplayMode::Oneline
+        && !display_options.recurse
+        && !display_options.inode
+        && !display_options.blocks
+        && !display_options.total
+        && display_options.max_entries.is_none();
+
+    display_options.sort_keys.is_empty()
+        && !display_options.group_dirs_first
+        && !display_options.group_dirs_last
+        && (bare_names || plain_lines)
+}
+
+/// Passes each entry of `dir` that passes the filters to `write_entry` as soon as it's read, for
+/// listings that `can_stream`. Like `open_entries`, a directory that can't be opened is reported
+/// and counted in `summary`.
+fn stream_entries(
+    dir: &Path,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    summary: &mut Summary,
+    mut write_entry: impl FnMut(&Entry) -> Result<(), MinilsError>,
+) -> Result<(), MinilsError> {
+    let entries = match unsorted_entries(dir, display_options, filtering_options) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("minils: {err}");
+            summary.unopened += 1;
+            return Ok(());
+        }
+    };
+
+    for entry in dot_entries(dir, filtering_options)
+        .into_iter()
+        .chain(entries)
+        .filter(|entry| matches_filters(entry, filtering_options))
+    {
+        if let Some(metadata) = &entry.metadata {
+            summary.count(metadata);
+        }
+        write_entry(&entry)?;
+    }
+
+    Ok(())
+}
+
+/// `read_entries`, except that a directory that can't be opened is reported and counted in
+/// `summary`, and the listing goes on without it
+fn open_entries(
+    dir: &Path,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    summary: &mut Summary,
+) -> Option<Vec<Entry>> {
+    match read_entries(dir, display_options, filtering_options) {
+        Ok(entries) => Some(entries),
+        Err(err) => {
+            eprintln!("minils: {err}");
+            summary.unopened += 1;
+            None
+        }
+    }
+}
+
+/// The `.` and `..` entries of `dir` if `--all` asked for them. ReadDir never yields these, so
+/// they're added back by hand; they always come first and are never recursed into.
+fn dot_entries(dir: &Path, filtering_options: &FilteringOptions) -> Vec<Entry> {
+    let mut dot_entries: Vec<Entry> = Vec::new();
+
+    if !filtering_options.all {
+        return dot_entries;
+    }
+
+    for name in [".", ".."] {
+        let path = dir.join(name);
+
+        match fs::metadata(&path) {
+            Ok(metadata) => dot_entries.push(Entry {
+                path,
+                name: name.to_string(),
+                file_type: metadata.file_type(),
+                metadata: Some(metadata),
+            }),
+            Err(err) => report(format!("reading metadata for '{}'", path.display()), err),
+        }
+    }
+
+    dot_entries
+}
+
+/// Whether an entry survives `--only-dirs`/`--only-files`/`--only-links`, `--ext` and the time
+/// and size filters. Hidden entries have already been dropped by `read_entries`, so these only
+/// narrow what's left.
+fn matches_filters(entry: &Entry, filtering_options: &FilteringOptions) -> bool {
+    has_listed_extension(entry, filtering_options)
+        && matches_filters_but_extension(entry, filtering_options)
+}
+
+/// Whether `--ext` lets an entry through, which only files with one of its extensions get
+fn has_listed_extension(entry: &Entry, filtering_options: &FilteringOptions) -> bool {
+    filtering_options.extensions.is_empty()
+        || !entry.file_type.is_dir()
+            && filtering_options
+                .extensions
+                .contains(&extension_of(&entry.name).to_lowercase())
+}
+
+/// `matches_filters` without `--ext`
+fn matches_filters_but_extension(entry: &Entry, filtering_options: &FilteringOptions) -> bool {
+    // Directories are exempt, since their own size says nothing about their contents
+    if (filtering_options.min_size.is_some() || filtering_options.max_size.is_some())
+        && !entry.file_type.is_dir()
+    {
+        let size = match &entry.metadata {
+            Some(metadata) => metadata.size(),
+            None => return false,
+        };
+
+        if filtering_options
+            .min_size
+            .is_some_and(|min_size| size < min_size)
+            || filtering_options
+                .max_size
+                .is_some_and(|max_size| size > max_size)
+        {
+            return false;
+        }
+    }
+
+    if filtering_options.newer_than.is_some() || filtering_options.older_than.is_some() {
+        // Times in the future count as brand new
+        let age = match entry_time(entry, &TimeField::Modified) {
+            Some(modified) => SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or(Duration::ZERO),
+            None => return false,
+        };
+
+        if filtering_options
+            .newer_than
+            .is_some_and(|newer_than| age > newer_than)
+            || filtering_options
+                .older_than
+                .is_some_and(|older_than| age < older_than)
+        {
+            return false;
+        }
+    }
+
+    if filtering_options.only_dirs {
+        entry.file_type.is_dir()
+    } else if filtering_options.only_files {
+        entry.file_type.is_file()
+    } else if filtering_options.only_links {
+        entry.file_type.is_symlink()
+    } else {
+        true
+    }
+}
+
+/// Identifies a directory however it was reached, by its device and inode numbers
+fn dir_identity(metadata: &fs::Metadata) -> (u64, u64) {
+    (metadata.dev(), metadata.ino())
+}
+
+/// Whether descending into `entry` would go round in a cycle, back into one of the directories
+/// it's nested in. Only possible when `--dereference` follows links to directories. A cycle is
+/// reported and counted in `summary`.
+fn is_cycle<'a>(
+    entry: &Entry,
+    mut open_dirs: impl Iterator<Item = &'a Option<(u64, u64)>>,
+    summary: &mut Summary,
+) -> bool {
+    let Some(identity) = entry.metadata.as_ref().map(dir_identity) else {
+        return false;
+    };
+    let cycle = open_dirs.any(|&open_dir| open_dir == Some(identity));
+
+    if cycle {
+        eprintln!("minils: {}", MinilsError::AlreadyListed(entry.path.clone()));
+        summary.cycles += 1;
+    }
+
+    cycle
+}
+
+/// Prints the path of every entry under `dir` whose name matches `--find`, descending like
+/// `list_entries` does under `--recurse`
+fn find_entries(
+    dir: &Path,
+    open_dirs: &mut Vec<Option<(u64, u64)>>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> Result<Summary, MinilsError> {
+    let mut summary = Summary::default();
+    let Some(pattern) = &filtering_options.find else {
+        return Ok(summary);
+    };
+    let depth = open_dirs.len();
+
+    let Some(entries) = open_entries(dir, display_options, filtering_options, &mut summary) else {
+        return Ok(summary);
+    };
 
     for entry in entries {
-        let entry = entry.unwrap_or_else(err_handling);
-        let entry_type = entry.file_type().unwrap_or_else(err_handling);
-
-        let entry_name = match entry.file_name().into_string() {
-            Ok(entry_name) => entry_name,
-            Err(os_entry_name) => {
-                eprintln!(
-                    "Invalid Unicode in name {}",
-                    os_entry_name.to_string_lossy()
-                );
-                process::exit(1);
+        if glob_match(pattern, &entry.name) && matches_filters(&entry, filtering_options) {
+            if let Some(metadata) = &entry.metadata {
+                summary.count(metadata);
+            }
+            write_found(&entry, display_options, use_color, ls_colors, out)?;
+        }
+
+        if !entry.file_type.is_dir()
+            || display_options
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+            || is_cycle(&entry, open_dirs.iter(), &mut summary)
+            || is_other_filesystem(&entry, open_dirs.first(), display_options)
+        {
+            continue;
+        }
+
+        open_dirs.push(entry.metadata.as_ref().map(dir_identity));
+        summary += find_entries(
+            &entry.path,
+            open_dirs,
+            display_options,
+            filtering_options,
+            use_color,
+            ls_colors,
+            out,
+        )?;
+        open_dirs.pop();
+    }
+
+    Ok(summary)
+}
+
+/// Writes a path found by `--find` on a line of its own, with only its last component colored
+fn write_found(
+    entry: &Entry,
+    display_options: &DisplayOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let entry_color = paint(ls_colors.entry_color(entry), use_color);
+    let reset = paint("\x1b[0m", use_color);
+    let quoting_style = &display_options.quoting_style;
+
+    let (parent, name) = match (entry.path.parent(), entry.path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            (format!("{}/", parent.display()), name.to_string_lossy())
+        }
+        _ => (String::new(), entry.path.to_string_lossy()),
+    };
+
+    writeln!(
+        out,
+        "{}{entry_color}{}{reset}",
+        quote_name(&parent, quoting_style),
+        quote_name(&name, quoting_style)
+    )
+}
+
+/// Whether `--mounts` keeps the descent out of `entry` because it's on another filesystem than
+/// the operand, which `root` identifies
+fn is_other_filesystem(
+    entry: &Entry,
+    root: Option<&Option<(u64, u64)>>,
+    display_options: &DisplayOptions,
+) -> bool {
+    if !display_options.mounts {
+        return false;
+    }
+
+    match (&entry.metadata, root) {
+        (Some(metadata), Some(Some((root_dev, _)))) => metadata.dev() != *root_dev,
+        _ => false,
+    }
+}
+
+/// Lists `dir`. `open_dirs` identifies `dir` and each directory above it up to the operand, so
+/// its length is how deep into the recursion `dir` is, the operand itself being 1.
+fn list_entries(
+    dir: &Path,
+    open_dirs: &mut Vec<Option<(u64, u64)>>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> Result<Summary, MinilsError> {
+    let mut summary = Summary::default();
+
+    if can_stream(display_options) {
+        // Only long mode and the leading columns need anything measured
+        let columns = Columns::measure(iter::empty(), display_options, filtering_options);
+        stream_entries(
+            dir,
+            display_options,
+            filtering_options,
+            &mut summary,
+            |entry| {
+                Ok(print_entry(
+                    entry,
+                    display_options,
+                    &columns,
+                    use_color,
+                    ls_colors,
+                    out,
+                )?)
+            },
+        )?;
+
+        return Ok(summary);
+    }
+
+    let Some(visible) = open_entries(dir, display_options, filtering_options, &mut summary) else {
+        return Ok(summary);
+    };
+    let dot_entries = dot_entries(dir, filtering_options);
+
+    let mut displayed: Vec<&Entry> = dot_entries
+        .iter()
+        .chain(&visible)
+        .filter(|entry| matches_filters(entry, filtering_options))
+        .collect();
+
+    // Everything after the cap goes uncounted too, but -R still descends into it
+    let mut left_out = 0;
+    if let Some(max_entries) = display_options.max_entries {
+        left_out = displayed.len().saturating_sub(max_entries);
+        displayed.truncate(max_entries);
+    }
+
+    let columns = Columns::measure(
+        displayed.iter().copied(),
+        display_options,
+        filtering_options,
+    );
+    for metadata in displayed.iter().filter_map(|entry| entry.metadata.as_ref()) {
+        summary.count(metadata);
+    }
+
+    if matches!(
+        display_options.mode,
+        DisplayMode::Grid | DisplayMode::Across
+    ) {
+        // Like ls, the default grid falls back to a single column when it isn't going to a
+        // terminal, while -x, like -m, still wraps at $COLUMNS or 80
+        let width = display_options.width.or_else(|| {
+            (display_options.mode == DisplayMode::Across || io::stdout().is_terminal())
+                .then(terminal_width)
+        });
+
+        print_grid(
+            &displayed,
+            width,
+            display_options,
+            &columns,
+            use_color,
+            ls_colors,
+            out,
+        )?;
+    } else if display_options.mode == DisplayMode::Comma {
+        print_commas(
+            &displayed,
+            display_options.width.unwrap_or_else(terminal_width),
+            display_options,
+            &columns,
+            use_color,
+            ls_colors,
+            out,
+        )?;
+    } else {
+        if display_options.mode == DisplayMode::Long {
+            // Like ls, in the same blocks as -s
+            let allocated: u64 = displayed
+                .iter()
+                .filter_map(|entry| entry.metadata.as_ref())
+                .map(|metadata| metadata.blocks() * 512)
+                .sum();
+            writeln!(
+                out,
+                "total {}",
+                allocated.div_ceil(display_options.block_size)
+            )?;
+
+            if !display_options.no_header && !displayed.is_empty() {
+                write_long_header(&columns, display_options, use_color, out)?;
             }
+        }
+
+        for entry in displayed {
+            print_entry(entry, display_options, &columns, use_color, ls_colors, out)?;
+        }
+    }
+
+    if left_out > 0 {
+        writeln!(out, "... and {left_out} more")?;
+    }
+
+    let depth = open_dirs.len();
+
+    if !display_options.recurse
+        || display_options
+            .max_depth
+            .is_some_and(|max_depth| depth >= max_depth)
+    {
+        return Ok(summary);
+    }
+
+    // Linked directories are only descended into under --dereference, since DirEntry::file_type
+    // doesn't follow symlinks. `.` and `..` come from dot_entries rather than `visible`, so -a
+    // lists them without ever descending into them.
+    for entry in visible.iter().filter(|entry| entry.file_type.is_dir()) {
+        if is_cycle(entry, open_dirs.iter(), &mut summary)
+            || is_other_filesystem(entry, open_dirs.first(), display_options)
+        {
+            continue;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "{}:", entry.path.display())?;
+
+        open_dirs.push(entry.metadata.as_ref().map(dir_identity));
+        summary += list_entries(
+            &entry.path,
+            open_dirs,
+            display_options,
+            filtering_options,
+            use_color,
+            ls_colors,
+            out,
+        )?;
+        open_dirs.pop();
+    }
+
+    Ok(summary)
+}
+
+/// How many entries `list_entries` would show for `dir`, descending and capping them the same
+/// way, for `--count-only`
+fn count_entries(
+    dir: &Path,
+    open_dirs: &mut Vec<Option<(u64, u64)>>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    summary: &mut Summary,
+) -> usize {
+    let Some(visible) = open_entries(dir, display_options, filtering_options, summary) else {
+        return 0;
+    };
+    let depth = open_dirs.len();
+
+    let mut count = dot_entries(dir, filtering_options)
+        .iter()
+        .chain(&visible)
+        .filter(|entry| matches_filters(entry, filtering_options))
+        .count();
+    if let Some(max_entries) = display_options.max_entries {
+        count = count.min(max_entries);
+    }
+
+    if !display_options.recurse
+        || display_options
+            .max_depth
+            .is_some_and(|max_depth| depth >= max_depth)
+    {
+        return count;
+    }
+
+    for entry in visible.iter().filter(|entry| entry.file_type.is_dir()) {
+        if is_cycle(entry, open_dirs.iter(), summary)
+            || is_other_filesystem(entry, open_dirs.first(), display_options)
+        {
+            continue;
+        }
+
+        open_dirs.push(entry.metadata.as_ref().map(dir_identity));
+        count += count_entries(
+            &entry.path,
+            open_dirs,
+            display_options,
+            filtering_options,
+            summary,
+        );
+        open_dirs.pop();
+    }
+
+    count
+}
+
+/// Prints the contents of `dir` as one level of a `--tree`, descending into subdirectories as
+/// it goes. `ancestors` holds, for `dir` and each level above it up to the root, whether that
+/// directory was the last entry of its parent, which decides if its part of the prefix carries
+/// on the `│` line, and its identity to catch cycles. The root's flag goes unused.
+fn print_tree(
+    dir: &Path,
+    ancestors: &mut Vec<(bool, Option<(u64, u64)>)>,
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    use_color: bool,
+    ls_colors: &LsColors,
+    out: &mut impl Write,
+) -> Result<Summary, MinilsError> {
+    let mut summary = Summary::default();
+
+    if display_options
+        .tree_depth
+        .is_some_and(|depth| ancestors.len() > depth)
+    {
+        return Ok(summary);
+    }
+
+    let Some(entries) = open_entries(dir, display_options, filtering_options, &mut summary) else {
+        return Ok(summary);
+    };
+    // Unlike other listings, the tree keeps the directories that --ext drops, or it would lose
+    // the branches leading to the files it picks
+    let displayed: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| {
+            (entry.file_type.is_dir() || has_listed_extension(entry, filtering_options))
+                && matches_filters_but_extension(entry, filtering_options)
+        })
+        .collect();
+
+    let prefix: String = ancestors
+        .iter()
+        .skip(1)
+        .map(|&(last, _)| if last { "    " } else { "│   " })
+        .collect();
+    let reset = paint("\x1b[0m", use_color);
+
+    for (i, entry) in displayed.iter().enumerate() {
+        let last = i + 1 == displayed.len();
+        let connector = if last { "└── " } else { "├── " };
+        let mode = entry.mode();
+        let entry_color = paint(ls_colors.entry_color(entry), use_color);
+        let target = link_target(entry, display_options);
+        let indicator = if target.is_some() {
+            ""
+        } else {
+            indicator(&entry.file_type, mode, &display_options.indicator_style)
+        };
+
+        let (link_start, link_end) = hyperlink(entry, display_options, use_color);
+        write!(
+            out,
+            "{prefix}{connector}{link_start}{entry_color}{entry_name}{indicator}{reset}{link_end}",
+            entry_name = display_name(entry, display_options)
+        )?;
+        if let Some(target) = &target {
+            write_link_target(entry, target, entry_color, use_color, out)?;
+        }
+        writeln!(out)?;
+
+        if let Some(metadata) = &entry.metadata {
+            summary.count(metadata);
+        }
+
+        // Linked directories stay leaves unless --dereference resolved them
+        if entry.file_type.is_dir()
+            && !is_cycle(
+                entry,
+                ancestors.iter().map(|(_, identity)| identity),
+                &mut summary,
+            )
+            && !is_other_filesystem(
+                entry,
+                ancestors.first().map(|(_, identity)| identity),
+                display_options,
+            )
+        {
+            ancestors.push((last, entry.metadata.as_ref().map(dir_identity)));
+            summary += print_tree(
+                &entry.path,
+                ancestors,
+                display_options,
+                filtering_options,
+                use_color,
+                ls_colors,
+                out,
+            )?;
+            ancestors.pop();
+        }
+    }
+
+    Ok(summary)
+}
+
+/// File operands by the path given, followed by the contents of each directory operand, for
+/// output formats that list everything as one flat sequence
+fn flat_entries(
+    files: Vec<Entry>,
+    dirs: &[PathBuf],
+    display_options: &DisplayOptions,
+    filtering_options: &FilteringOptions,
+    summary: &mut Summary,
+) -> Vec<Entry> {
+    let mut entries = files;
+
+    for dir in dirs {
+        let Some(dir_entries) = open_entries(dir, display_options, filtering_options, summary)
+        else {
+            continue;
+        };
+        entries.extend(
+            dot_entries(dir, filtering_options)
+                .into_iter()
+                .chain(dir_entries)
+                .filter(|entry| matches_filters(entry, filtering_options)),
+        );
+    }
+
+    entries
+}
+
+/// Writes the listing as a JSON array with one object per entry, in the order of
+/// `flat_entries`. `--recurse` and `--tree` don't apply.
+fn write_json(entries: &[Entry], out: &mut impl Write) -> Result<(), MinilsError> {
+    write!(out, "[")?;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "," };
+        let entry_type = if entry.file_type.is_dir() {
+            "dir"
+        } else if entry.file_type.is_file() {
+            "file"
+        } else if entry.file_type.is_symlink() {
+            "symlink"
+        } else {
+            "other"
         };
 
-        if filtering_options.only_dirs && entry_type.is_dir() {
-            if filtering_options.all {
-                print_entry(&entry, &entry_type, &entry_name, &display_options);
-            } else if !entry_name.starts_with(".") {
-                print_entry(&entry, &entry_type, &entry_name, &display_options);
+        write!(out, "{separator}\n  {{\"name\": ")?;
+        write_json_string(&entry.name, out)?;
+        write!(out, ", \"type\": \"{entry_type}\"")?;
+
+        match &entry.metadata {
+            Some(metadata) => {
+                write!(out, ", \"size\": {}", metadata.size())?;
+                write!(
+                    out,
+                    ", \"mode\": \"{:04o}\"",
+                    metadata.permissions().mode() & 0o7777
+                )?;
             }
-        } else if filtering_options.only_files && entry_type.is_file() {
-            if filtering_options.all {
-                print_entry(&entry, &entry_type, &entry_name, &display_options);
-            } else if !entry_name.starts_with(".") {
-                print_entry(&entry, &entry_type, &entry_name, &display_options);
+            None => write!(out, ", \"size\": null, \"mode\": null")?,
+        }
+
+        match entry_time(entry, &TimeField::Modified)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        {
+            Some(mtime) => write!(out, ", \"mtime\": {}", mtime.as_secs())?,
+            None => write!(out, ", \"mtime\": null")?,
+        }
+
+        let target = if entry.file_type.is_symlink() {
+            fs::read_link(&entry.path).ok()
+        } else {
+            None
+        };
+        match target {
+            Some(target) => {
+                write!(out, ", \"target\": ")?;
+                write_json_string(&target.to_string_lossy(), out)?;
             }
-        } else if !filtering_options.all && !entry_name.starts_with(".") {
-            print_entry(&entry, &entry_type, &entry_name, &display_options);
+            None => write!(out, ", \"target\": null")?,
+        }
+
+        write!(out, "}}")?;
+    }
+
+    if !entries.is_empty() {
+        writeln!(out)?;
+    }
+    writeln!(out, "]")?;
+
+    Ok(())
+}
+
+/// Writes just the name of each entry followed by `terminator`, in the order of `flat_entries`,
+/// for `--zero` and `--raw`. Nothing is quoted or escaped, and like `--json`, `--recurse` and
+/// `--tree` don't apply.
+fn write_bare_names(
+    entries: &[Entry],
+    terminator: &[u8],
+    out: &mut impl Write,
+) -> Result<(), MinilsError> {
+    for entry in entries {
+        // Names were decoded lossily, so the original bytes come from the path where it has them
+        let raw_name = [
+            entry.path.as_os_str(),
+            entry.path.file_name().unwrap_or_default(),
+        ]
+        .into_iter()
+        .find(|raw_name| raw_name.to_string_lossy() == entry.name);
+
+        out.write_all(raw_name.map_or(entry.name.as_bytes(), OsStrExt::as_bytes))?;
+        out.write_all(terminator)?;
+    }
+
+    Ok(())
+}
+
+/// Writes statistics about the entries of `flat_entries` in place of the entries themselves:
+/// how many there are of each type, how large everything but the directories is together, and
+/// which file is the largest and which the newest
+fn write_report(
+    entries: &[Entry],
+    display_options: &DisplayOptions,
+    out: &mut impl Write,
+) -> Result<(), MinilsError> {
+    let (mut files, mut dirs, mut symlinks, mut others) = (0, 0, 0, 0);
+    let mut size = 0;
+    let mut largest: Option<(&Entry, u64)> = None;
+    let mut newest: Option<(&Entry, SystemTime)> = None;
+
+    for entry in entries {
+        if entry.file_type.is_dir() {
+            dirs += 1;
+            continue;
+        } else if entry.file_type.is_file() {
+            files += 1;
+        } else if entry.file_type.is_symlink() {
+            symlinks += 1;
         } else {
-            print_entry(&entry, &entry_type, &entry_name, &display_options);
+            others += 1;
+        }
+
+        let Some(metadata) = &entry.metadata else {
+            continue;
+        };
+        size += metadata.size();
+
+        if !entry.file_type.is_file() {
+            continue;
         }
+
+        // Ties go to whichever comes first in the listing
+        if largest.is_none_or(|(_, most)| metadata.size() > most) {
+            largest = Some((entry, metadata.size()));
+        }
+        if let Some(time) = display_options.time_field.of(metadata) {
+            if newest.is_none_or(|(_, latest)| time > latest) {
+                newest = Some((entry, time));
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "{}: {}, {}, {}, {others} other",
+        counted(entries.len(), "entry", "entries"),
+        counted(files, "file", "files"),
+        counted(dirs, "directory", "directories"),
+        counted(symlinks, "symlink", "symlinks"),
+    )?;
+    writeln!(out, "Total size: {}", format_total(size, display_options))?;
+
+    let quoting_style = &display_options.quoting_style;
+    if let Some((entry, most)) = largest {
+        writeln!(
+            out,
+            "Largest file: {} ({})",
+            quote_name(&entry.name, quoting_style),
+            format_total(most, display_options)
+        )?;
+    }
+    if let Some((entry, latest)) = newest {
+        writeln!(
+            out,
+            "Newest file: {} ({})",
+            quote_name(&entry.name, quoting_style),
+            format_time(latest, SystemTime::now(), &display_options.time_style).trim()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes `text` as a quoted JSON string
+fn write_json_string(text: &str, out: &mut impl Write) -> io::Result<()> {
+    write!(out, "\"")?;
+
+    for c in text.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{c}")?,
+        }
+    }
+
+    write!(out, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a command line the way `main` does, starting from the same defaults
+    fn parse(
+        args: &[&str],
+    ) -> Result<(Vec<PathBuf>, DisplayOptions, FilteringOptions), MinilsError> {
+        let args: Vec<String> = ["minils"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect();
+        let mut display_options = DisplayOptions {
+            mode: DisplayMode::Grid,
+            recurse: false,
+            max_depth: None,
+            mounts: false,
+            reverse: false,
+            sort_keys: vec![SortKey::Name],
+            group_dirs_first: false,
+            group_dirs_last: false,
+            case_sensitive: false,
+            time_field: TimeField::Modified,
+            time_style: TimeStyle::Default,
+            color: ColorMode::Auto,
+            quoting_style: QuotingStyle::Auto,
+            indicator_style: IndicatorStyle::None,
+            numeric_uid_gid: false,
+            author: false,
+            inode: false,
+            blocks: false,
+            block_size: 1024,
+            human_readable: false,
+            bytes: false,
+            du: false,
+            dir_counts: false,
+            color_scale: false,
+            icons: false,
+            hyperlink: false,
+            allocated_size: false,
+            octal_permissions: false,
+            dereference: false,
+            dereference_command_line: false,
+            tree: false,
+            tree_depth: None,
+            max_entries: None,
+            width: None,
+            gutter: 2,
+            json: false,
+            zero: false,
+            raw: false,
+            summary: false,
+            count_only: false,
+            link_target: false,
+            total: false,
+            no_header: false,
+            no_filename: false,
+            pager: false,
+        };
+        let mut filtering_options = FilteringOptions {
+            all: false,
+            almost_all: false,
+            list_dirs: false,
+            only_dirs: false,
+            only_files: false,
+            only_links: false,
+            ignore: Vec::new(),
+            extensions: Vec::new(),
+            newer_than: None,
+            older_than: None,
+            min_size: None,
+            max_size: None,
+            find: None,
+        };
+
+        let operands = parse_arguments(&args, &mut display_options, &mut filtering_options)?;
+        Ok((operands, display_options, filtering_options))
+    }
+
+    #[test]
+    fn the_default_operand_is_the_current_directory() {
+        let (operands, _, _) = parse(&["-l"]).unwrap();
+        assert_eq!(operands, [PathBuf::from(".")]);
+    }
+
+    #[test]
+    fn operands_may_come_anywhere() {
+        let (operands, display_options, filtering_options) = parse(&["src", "-l"]).unwrap();
+        assert_eq!(operands, [PathBuf::from("src")]);
+        assert!(display_options.mode == DisplayMode::Long);
+        assert!(!filtering_options.all);
+
+        let (operands, display_options, filtering_options) = parse(&["-l", "src", "-a"]).unwrap();
+        assert_eq!(operands, [PathBuf::from("src")]);
+        assert!(display_options.mode == DisplayMode::Long);
+        assert!(filtering_options.all);
+    }
+
+    #[test]
+    fn parse_errors_are_returned() {
+        assert!(matches!(
+            parse(&["--no-such-option"]),
+            Err(MinilsError::InvalidOption(option)) if option == "--no-such-option"
+        ));
+        assert!(matches!(
+            parse(&["-l", "-K"]),
+            Err(MinilsError::InvalidOption(option)) if option == "K"
+        ));
+        assert!(matches!(
+            parse(&["--width"]),
+            Err(MinilsError::MissingArgument(option)) if option == "--width"
+        ));
+        assert!(matches!(
+            parse(&["-w"]),
+            Err(MinilsError::MissingArgument(option)) if option == "-w"
+        ));
+        assert!(matches!(parse(&["-"]), Err(MinilsError::MissingOption)));
+    }
+
+    #[test]
+    fn sort_takes_its_value_either_way() {
+        for args in [&["--sort=size"][..], &["--sort", "size"]] {
+            let (_, display_options, _) = parse(args).unwrap();
+            assert!(display_options.sort_keys == [SortKey::Size], "{args:?}");
+        }
+
+        let (_, display_options, _) = parse(&["--sort=size,name"]).unwrap();
+        assert!(display_options.sort_keys == [SortKey::Size, SortKey::Name]);
+    }
+
+    #[test]
+    fn width_takes_its_value_either_way() {
+        for args in [
+            &["-w", "100"][..],
+            &["-w100"],
+            &["--width=100"],
+            &["--width", "100"],
+        ] {
+            let (operands, display_options, _) = parse(args).unwrap();
+            assert_eq!(display_options.width, Some(100), "{args:?}");
+            assert_eq!(operands, [PathBuf::from(".")], "{args:?}");
+        }
+    }
+
+    #[test]
+    fn long_options_split_at_the_first_equals_sign() {
+        let (_, _, filtering_options) = parse(&["--ignore=a=b", "--ignore", "c"]).unwrap();
+        assert_eq!(filtering_options.ignore, ["a=b", "c"]);
+
+        let (_, display_options, _) = parse(&["--time-style=long-iso"]).unwrap();
+        assert!(matches!(display_options.time_style, TimeStyle::LongIso));
+
+        // Flags don't take a value at all
+        assert!(matches!(
+            parse(&["--oneline=yes"]),
+            Err(MinilsError::InvalidOption(option)) if option == "--oneline=yes"
+        ));
+    }
+
+    #[test]
+    fn format_size_switches_units_at_powers_of_ten() {
+        assert_eq!(format_size(999, false), " 999B");
+        assert_eq!(format_size(1000, false), "  1KB");
+        assert_eq!(format_size(999_999, false), "999KB");
+        assert_eq!(format_size(1_000_000, false), "  1MB");
+        assert_eq!(format_size(12 * 10u64.pow(12), false), " 12TB");
+    }
+
+    #[test]
+    fn format_size_binary_has_one_decimal() {
+        assert_eq!(format_size(1023, true), "    1023B");
+        assert_eq!(format_size(1536, true), "   1.5KiB");
+        assert_eq!(format_size(1_048_576, true), "   1.0MiB");
+        assert_eq!(format_size(3 * 1024u64.pow(4), true), "   3.0TiB");
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// 2024-03-05 07:08:09.123456789 UTC, with the local time zone set to UTC so it reads the
+    /// same wherever the tests run. It has to be set before `localtime_r` first looks at it.
+    fn fixed_time() -> SystemTime {
+        static UTC: std::sync::Once = std::sync::Once::new();
+        UTC.call_once(|| env::set_var("TZ", "UTC"));
+
+        UNIX_EPOCH + Duration::new(1_709_622_489, 123_456_789)
+    }
+
+    #[test]
+    fn format_time_iso_styles() {
+        let time = fixed_time();
+        let now = time + DAY;
+
+        assert_eq!(
+            format_time(time, now, &TimeStyle::LongIso),
+            "2024-03-05 07:08"
+        );
+        assert_eq!(
+            format_time(time, now, &TimeStyle::FullIso),
+            "2024-03-05 07:08:09.123456789 +0000"
+        );
+        assert_eq!(format_time(time, now, &TimeStyle::Iso), "03-05 07:08");
+        assert_eq!(
+            format_time(time, now + 365 * DAY, &TimeStyle::Iso),
+            "2024-03-05 "
+        );
+    }
+
+    #[test]
+    fn format_time_default_shows_the_year_once_not_recent() {
+        let time = fixed_time();
+
+        assert_eq!(
+            format_time(time, time + DAY, &TimeStyle::Default),
+            "Mar  5 07:08"
+        );
+        assert_eq!(
+            format_time(time, time + 365 * DAY, &TimeStyle::Default),
+            "Mar  5  2024"
+        );
+    }
+
+    #[test]
+    fn format_age_uses_the_largest_whole_unit() {
+        let now = fixed_time();
+
+        assert_eq!(
+            format_age(now - Duration::from_secs(30), now),
+            "30 seconds ago"
+        );
+        assert_eq!(
+            format_age(now - Duration::from_secs(90 * 60), now),
+            "1 hour ago"
+        );
+        assert_eq!(format_age(now - 5 * DAY, now), "5 days ago");
+        assert_eq!(format_age(now - Duration::from_secs(5), now), "just now");
+        assert_eq!(format_age(now + DAY, now), "in the future");
     }
 
-    if display_options.grid {
-        println!();
+    #[test]
+    fn grid_columns_fit_the_widest_name() {
+        let widths = [3, 5, 4, 1];
+
+        // Each column takes 5 + 2, but the last one needs no gutter after it
+        assert_eq!(grid_columns(&widths, 26, 2), 4);
+        assert_eq!(grid_columns(&widths, 25, 2), 3);
+        assert_eq!(grid_columns(&widths, 18, 2), 2);
+        assert_eq!(grid_columns(&widths, 20, 5), 2);
+    }
+
+    #[test]
+    fn grid_columns_stay_between_one_and_the_name_count() {
+        assert_eq!(grid_columns(&[10, 20], 5, 2), 1);
+        assert_eq!(grid_columns(&[1, 1], 200, 2), 2);
+        assert_eq!(grid_columns(&[], 80, 2), 1);
+        assert_eq!(grid_columns(&[1, 1], usize::MAX, 2), 2);
+    }
+
+    #[test]
+    fn width_prefers_columns() {
+        assert_eq!(width_from(Some("120"), None), 120);
+        assert_eq!(width_from(Some("120"), Some(100)), 120);
+        assert_eq!(width_from(Some("0"), Some(100)), 100);
+        assert_eq!(width_from(Some("wide"), None), 80);
+        assert_eq!(width_from(None, None), 80);
+    }
+
+    #[test]
+    fn extension_is_after_the_last_dot_but_not_a_leading_one() {
+        assert_eq!(extension_of("a.rs"), "rs");
+        assert_eq!(extension_of("archive.tar.gz"), "gz");
+        assert_eq!(extension_of(".bashrc"), "");
+        assert_eq!(extension_of("Makefile"), "");
+        assert_eq!(extension_of("trailing."), "");
+    }
+
+    #[test]
+    fn versions_compare_numbers_by_value() {
+        let mut names = ["file20", "file10", "file2", "file1"];
+        names.sort_by(|a, b| compare_versions(a, b, false));
+
+        assert_eq!(names, ["file1", "file2", "file10", "file20"]);
+    }
+
+    #[test]
+    fn versions_ignore_leading_zeros() {
+        assert_eq!(compare_versions("v007", "v10", false), Ordering::Less);
+        assert_eq!(compare_versions("v010", "v9", false), Ordering::Greater);
+        // Equal values fall back to comparing the names, so the order is still total
+        assert_eq!(compare_versions("v007", "v7", false), Ordering::Less);
+    }
+
+    #[test]
+    fn versions_fold_case_unless_asked_not_to() {
+        assert_eq!(compare_versions("B2", "a10", false), Ordering::Greater);
+        assert_eq!(compare_versions("B2", "a10", true), Ordering::Less);
+        assert_eq!(compare_versions("File2", "file10", false), Ordering::Less);
+    }
+
+    #[test]
+    fn glob_expands_against_the_filesystem() {
+        let dir = env::temp_dir().join(format!("minils-glob-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.txt", "b.txt", "c.rs"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let mut matches = expand_glob(&format!("{}/*.txt", dir.display())).unwrap();
+        matches.sort();
+        assert_eq!(matches, [dir.join("a.txt"), dir.join("b.txt")]);
+
+        let none = expand_glob(&format!("{}/*.none", dir.display()));
+        assert!(matches!(none, Err(MinilsError::NoMatches(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn glob_match_sets() {
+        assert!(glob_match("[a-c]", "b"));
+        assert!(!glob_match("[a-c]", "d"));
+        assert!(glob_match("[!x]", "y"));
+        assert!(!glob_match("[!x]", "x"));
+        assert!(glob_match("[]]", "]"));
+        assert!(!glob_match("[]]", "a"));
+    }
+
+    #[test]
+    fn glob_match_stars() {
+        assert!(glob_match("foo*", "foo"));
+        assert!(glob_match("foo*", "foobar"));
+        assert!(!glob_match("foo*", "fo"));
+        assert!(glob_match("*a*b", "xaxab"));
+        assert!(glob_match("?.rs", "a.rs"));
+    }
+
+    #[test]
+    fn glob_match_unclosed_bracket_is_literal() {
+        assert!(glob_match("[ab", "[ab"));
+        assert!(!glob_match("[ab", "a"));
+    }
+
+    /// File types of a real directory and a real regular file, since `fs::FileType` can't be
+    /// built directly
+    fn dir_and_file_types() -> (fs::FileType, fs::FileType) {
+        let dir = fs::metadata(env::temp_dir()).unwrap().file_type();
+        let file = fs::metadata(env::current_exe().unwrap())
+            .unwrap()
+            .file_type();
+        (dir, file)
+    }
+
+    #[test]
+    fn ls_colors_parse_directories_and_suffixes() {
+        let (dir, file) = dir_and_file_types();
+        let ls_colors = LsColors::parse("di=01;34:*.tar=01;31:bogus:xx=1");
+
+        assert_eq!(ls_colors.color_for("x.tar", &file, 0o644), "\x1b[01;31m");
+        assert_eq!(ls_colors.color_for("X.TAR", &file, 0o644), "\x1b[01;31m");
+        assert_eq!(ls_colors.color_for("x.tar", &dir, 0o755), "\x1b[01;34m");
+        assert_eq!(
+            ls_colors.color_for("x.txt", &file, 0o644),
+            LsColors::default().file
+        );
+    }
+
+    #[test]
+    fn color_for_uses_extensions_then_the_execute_bit() {
+        let (_, file) = dir_and_file_types();
+        let ls_colors = LsColors::default();
+
+        assert_eq!(ls_colors.color_for("photo.png", &file, 0o644), "\x1b[1;35m");
+        assert_eq!(
+            ls_colors.color_for("backup.tar.gz", &file, 0o644),
+            "\x1b[1;31m"
+        );
+        assert_eq!(
+            ls_colors.color_for("build.sh", &file, 0o755),
+            ls_colors.executable
+        );
+    }
+
+    #[test]
+    fn octal_permissions_have_four_digits() {
+        for (mode, expected) in [(0o100644, "0644"), (0o100755, "0755"), (0o104755, "4755")] {
+            let mut out = Vec::new();
+            write_octal_permissions(mode, &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn sizes_take_binary_units_in_either_case() {
+        assert_eq!(parse_size("500"), Some(500));
+        assert_eq!(parse_size("1K"), Some(1024));
+        assert_eq!(parse_size("10k"), Some(10 * 1024));
+        assert_eq!(parse_size("5M"), Some(5 * 1024 * 1024));
+        assert_eq!(parse_size("2T"), Some(2 << 40));
+        assert_eq!(parse_size("K"), None);
+        assert_eq!(parse_size("1.5M"), None);
+        assert_eq!(parse_size("99999999T"), None);
+    }
+
+    #[test]
+    fn paged_listings_go_to_the_pager() {
+        let dir = env::temp_dir().join(format!("minils-pager-{}", std::process::id()));
+        fs::create_dir_all(dir.join("listed")).unwrap();
+        for name in ["a", "b"] {
+            fs::write(dir.join("listed").join(name), "").unwrap();
+        }
+
+        let (_, display_options, filtering_options) = parse(&["-1"]).unwrap();
+        let paged = dir.join("paged");
+        let pager = format!("cat > '{}'", paged.display());
+        let status = run_paged(
+            vec![dir.join("listed")],
+            &display_options,
+            &filtering_options,
+            false,
+            &LsColors::default(),
+            &pager,
+        );
+
+        assert!(matches!(status, Ok(ExitCode::SUCCESS)));
+        assert_eq!(fs::read_to_string(&paged).unwrap(), "a\nb\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_say_what_was_being_done_and_to_what() {
+        let denied = || io::Error::from_raw_os_error(13);
+
+        assert_eq!(
+            MinilsError::CannotOpenDir(PathBuf::from("secret"), denied()).to_string(),
+            "cannot open directory 'secret': Permission denied"
+        );
+        assert_eq!(
+            MinilsError::Failed("reading link './link'".to_string(), denied()).to_string(),
+            "reading link './link': Permission denied"
+        );
+        assert_eq!(
+            MinilsError::CannotAccess(PathBuf::from("gone"), io::Error::from_raw_os_error(2))
+                .to_string(),
+            "cannot access 'gone': No such file or directory"
+        );
+    }
+
+    #[test]
+    fn size_colors_step_up_every_factor_of_1024() {
+        assert_eq!(size_color(0), size_color(10));
+        assert_eq!(size_color(10), size_color(1023));
+        assert_ne!(size_color(10), size_color(1024));
+        assert_ne!(size_color(10), size_color(10 << 30));
+        assert_ne!(size_color(1 << 20), size_color(10 << 30));
+    }
+
+    #[test]
+    fn other_filesystems_are_told_apart_by_device() {
+        let path = env::temp_dir();
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        let (dev, ino) = (metadata.dev(), metadata.ino());
+        let entry = Entry {
+            path,
+            name: String::from("tmp"),
+            file_type: metadata.file_type(),
+            metadata: Some(metadata),
+        };
+        let (_, mounts, _) = parse(&["-R", "--mounts"]).unwrap();
+        let (_, anywhere, _) = parse(&["-R"]).unwrap();
+
+        assert!(!is_other_filesystem(
+            &entry,
+            Some(&Some((dev, ino))),
+            &mounts
+        ));
+        assert!(is_other_filesystem(
+            &entry,
+            Some(&Some((dev + 1, ino))),
+            &mounts
+        ));
+        assert!(!is_other_filesystem(
+            &entry,
+            Some(&Some((dev + 1, ino))),
+            &anywhere
+        ));
+        // Without an identified root there's nothing to compare against
+        assert!(!is_other_filesystem(&entry, Some(&None), &mounts));
+        assert!(!is_other_filesystem(&entry, None, &mounts));
+    }
+
+    #[test]
+    fn icons_for_directories_and_rust_files() {
+        let (dir, file) = dir_and_file_types();
+
+        assert_eq!(icon_for("src", &dir), '\u{f07b}');
+        assert_eq!(icon_for("main.rs", &file), '\u{e7a8}');
+        assert_eq!(icon_for("MAIN.RS", &file), '\u{e7a8}');
+        assert_eq!(icon_for("notes", &file), '\u{f15b}');
     }
 }
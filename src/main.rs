@@ -1,43 +1,101 @@
-use minils;
 use std::env;
+use std::process::{self, ExitCode};
 
-fn main() {
+// Like ls, anything that stops the listing altogether exits with 2
+fn err_handling<T>(err: minils::MinilsError) -> T {
+    eprintln!("minils: {err}");
+    process::exit(2);
+}
+
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() == 2 {
-        match args[1].as_str() {
+    // These win over everything else on the command line, wherever they appear
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
             "--help" | "-?" => {
                 println!("{help_msg}", help_msg = minils::HELP);
-                return;
+                return ExitCode::SUCCESS;
             }
-            "--version" | "-v" => {
+            "--version" => {
                 println!(
                     "{name} - {description}",
                     name = env!("CARGO_PKG_NAME"),
                     description = env!("CARGO_PKG_DESCRIPTION")
                 );
                 println!("v{version}", version = env!("CARGO_PKG_VERSION"));
-                return;
+                return ExitCode::SUCCESS;
             }
             _ => (),
         }
     }
 
     let mut display_options = minils::DisplayOptions {
-        oneline: false,
-        grid: true,
-        long: false,
+        mode: minils::DisplayMode::Grid,
         recurse: false,
+        max_depth: None,
+        mounts: false,
+        reverse: false,
+        sort_keys: vec![minils::SortKey::Name],
+        group_dirs_first: false,
+        group_dirs_last: false,
+        case_sensitive: false,
+        time_field: minils::TimeField::Modified,
+        time_style: minils::TimeStyle::Default,
+        color: minils::ColorMode::Auto,
+        quoting_style: minils::QuotingStyle::Auto,
+        indicator_style: minils::IndicatorStyle::None,
+        numeric_uid_gid: false,
+        author: false,
+        inode: false,
+        blocks: false,
+        block_size: 1024,
+        human_readable: false,
+        bytes: false,
+        du: false,
+        dir_counts: false,
+        color_scale: false,
+        icons: false,
+        hyperlink: false,
+        allocated_size: false,
+        octal_permissions: false,
+        dereference: false,
+        dereference_command_line: false,
+        tree: false,
+        tree_depth: None,
+        max_entries: None,
+        width: None,
+        gutter: 2,
+        json: false,
+        zero: false,
+        raw: false,
+        summary: false,
+        count_only: false,
+        link_target: false,
+        total: false,
+        no_header: false,
+        no_filename: false,
+        pager: false,
     };
 
     let mut filtering_options = minils::FilteringOptions {
         all: false,
+        almost_all: false,
         list_dirs: false,
         only_dirs: false,
         only_files: false,
+        only_links: false,
+        ignore: Vec::new(),
+        extensions: Vec::new(),
+        newer_than: None,
+        older_than: None,
+        min_size: None,
+        max_size: None,
+        find: None,
     };
 
-    let entries = minils::parse_arguments(&args, &mut display_options, &mut filtering_options);
+    let operands = minils::parse_arguments(&args, &mut display_options, &mut filtering_options)
+        .unwrap_or_else(err_handling);
 
-    minils::run(entries, display_options, filtering_options);
+    minils::run(operands, display_options, filtering_options).unwrap_or_else(err_handling)
 }
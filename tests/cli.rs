@@ -0,0 +1,1482 @@
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A fresh, empty directory of its own for each test, so they can run in parallel
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("minils-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs minils in `dir` with stdout piped, as it would be in a script
+fn minils(dir: &Path, args: &[&str]) -> Output {
+    minils_with_env(dir, args, &[])
+}
+
+/// `minils` with some environment variables set
+fn minils_with_env(dir: &Path, args: &[&str], vars: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minils"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("COLUMNS")
+        .env_remove("LS_COLORS")
+        .env_remove("NO_COLOR")
+        .env_remove("BLOCK_SIZE")
+        .envs(vars.iter().copied())
+        .output()
+        .unwrap()
+}
+
+/// Creates an empty file in `dir` for each name
+fn touch(dir: &Path, names: &[&str]) {
+    for name in names {
+        fs::write(dir.join(name), "").unwrap();
+    }
+}
+
+/// Backdates the modification time of `path` by `hours`
+fn age(path: &Path, hours: u64) {
+    let time = SystemTime::now() - Duration::from_secs(hours * 60 * 60);
+    fs::File::open(path).unwrap().set_modified(time).unwrap();
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn later_sort_keys_break_ties_of_earlier_ones() {
+    let dir = fixture("sort-chain");
+    for (name, size) in [("a.rs", 10), ("b.rs", 30), ("c.txt", 20), ("d.txt", 5)] {
+        fs::write(dir.join(name), "x".repeat(size)).unwrap();
+    }
+
+    // Without a second key, ties in extension go to the name
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--sort=extension"])),
+        "a.rs\nb.rs\nc.txt\nd.txt\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--sort=extension,size"])),
+        "b.rs\na.rs\nc.txt\nd.txt\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--sort=size,extension"])),
+        "b.rs\nc.txt\na.rs\nd.txt\n"
+    );
+}
+
+/// `a`, `b` and `d/` at the top, `x`, `y` and `e/` in `d`, and `z` in `d/e`
+fn nested_fixture(name: &str) -> PathBuf {
+    let dir = fixture(name);
+    fs::create_dir_all(dir.join("d/e")).unwrap();
+    for file in ["a", "b", "d/x", "d/y", "d/e/z"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn count_only_counts_what_recurse_lists() {
+    let dir = nested_fixture("count-recurse");
+
+    let listing = stdout(&minils(&dir, &["-R", "-1"]));
+    let listed = listing
+        .lines()
+        .filter(|line| !line.is_empty() && !line.ends_with(':'))
+        .count();
+
+    assert_eq!(listed, 7);
+    assert_eq!(stdout(&minils(&dir, &["-R", "--count-only"])), "7\n");
+    assert_eq!(stdout(&minils(&dir, &["--count-only"])), "3\n");
+}
+
+#[test]
+fn count_only_follows_max_entries() {
+    let dir = nested_fixture("count-max-entries");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["--max-entries=2", "--count-only"])),
+        "2\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-R", "--max-entries=2", "--count-only"])),
+        "5\n"
+    );
+}
+
+#[test]
+fn count_only_with_only_files_or_only_dirs() {
+    let dir = nested_fixture("count-only-types");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["--only-files", "--count-only"])),
+        "2\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["--only-dirs", "--count-only"])),
+        "1\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-R", "--only-files", "--count-only"])),
+        "5\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-R", "--only-dirs", "--count-only"])),
+        "2\n"
+    );
+}
+
+#[test]
+fn bad_option_values_are_invalid_values() {
+    let dir = fixture("invalid-values");
+
+    for args in [
+        &["--sort=bogus"][..],
+        &["--time-style", "bogus"],
+        &["--quoting-style=bogus"],
+        &["--indicator-style=bogus"],
+        &["--width=0"],
+        &["--gutter=x"],
+        &["-l", "--gutter=18446744073709551615"],
+        &["--gutter=256"],
+        &["--color=bogus"],
+    ] {
+        let output = minils(&dir, args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            stderr.contains("Invalid value, expected"),
+            "{args:?}: {stderr}"
+        );
+    }
+}
+
+#[test]
+fn color_value_only_follows_an_equals_sign() {
+    let dir = fixture("color-value");
+    fs::create_dir(dir.join("never")).unwrap();
+    fs::write(dir.join("never/file"), "").unwrap();
+
+    let output = minils(&dir, &["--color", "never"]);
+
+    // `never` is the directory listed, and --color on its own means always
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("file\x1b[0m"));
+}
+
+#[test]
+fn recursing_into_a_link_cycle_stops_with_a_warning() {
+    let dir = fixture("link-cycle");
+    fs::create_dir(dir.join("a")).unwrap();
+    std::os::unix::fs::symlink("..", dir.join("a/link")).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minils"))
+        .args(["-RL", "."])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // A cycle that isn't caught never ends, so give up on it rather than hang
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("minils -RL kept going round the cycle");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stderr,
+        "minils: ./a/link: not listing already-listed directory\n"
+    );
+    assert_eq!(stdout(&output), "a\n\n./a:\nlink\n");
+}
+
+#[test]
+fn comma_lists_wrap_when_piped() {
+    let dir = fixture("comma-wrap");
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+
+    assert_eq!(stdout(&minils(&dir, &["-m"])), "a.txt, b.txt, c.txt\n");
+    assert_eq!(
+        stdout(&minils(&dir, &["-m", "-w", "14"])),
+        "a.txt, b.txt,\nc.txt\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minils"))
+        .arg("-m")
+        .current_dir(&dir)
+        .env("COLUMNS", "14")
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "a.txt, b.txt,\nc.txt\n");
+}
+
+#[test]
+fn across_fills_rows_where_the_grid_fills_columns() {
+    let dir = fixture("across");
+    for name in ["a", "b", "c", "d"] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+
+    assert_eq!(stdout(&minils(&dir, &["-G", "-w", "4"])), "a  c\nb  d\n");
+    assert_eq!(stdout(&minils(&dir, &["-x", "-w", "4"])), "a  b\nc  d\n");
+
+    // Piped without a width, only the default grid falls back to a single column
+    assert_eq!(stdout(&minils(&dir, &[])), "a\nb\nc\nd\n");
+    assert_eq!(stdout(&minils(&dir, &["-x"])), "a  b  c  d\n");
+}
+
+#[test]
+fn gutter_spaces_every_column() {
+    let dir = fixture("gutter");
+    fs::write(dir.join("a"), "").unwrap();
+
+    let header = |gutter: &str| {
+        let output = minils(&dir, &["-lis", gutter]);
+        let listing = stdout(&output);
+        listing.lines().nth(1).unwrap().trim_start().to_string()
+    };
+    assert!(header("--gutter=1").starts_with("Inode Blocks Permissions Links User "));
+    assert!(header("--gutter=4").starts_with("Inode    Blocks    Permissions   Links    User    "));
+
+    let oneline = stdout(&minils(&dir, &["-1", "-i", "--gutter=4"]));
+    let (_, name) = oneline.trim_start().split_once(' ').unwrap();
+    assert_eq!(name, "   a\n");
+}
+
+#[test]
+fn flat_outputs_filter_dot_entries_too() {
+    let dir = fixture("flat-dots");
+    fs::create_dir(dir.join("d")).unwrap();
+    fs::write(dir.join("f"), "").unwrap();
+
+    assert_eq!(stdout(&minils(&dir, &["-a", "-f", "--raw"])), "f\n");
+    assert_eq!(stdout(&minils(&dir, &["-a", "-f", "-0"])), "f\0");
+    assert_eq!(stdout(&minils(&dir, &["-a", "--raw"])), ".\n..\nd\nf\n");
+}
+
+#[test]
+fn json_escapes_names_and_shows_link_targets() {
+    let dir = fixture("json");
+    fs::write(dir.join("a\"b"), "hi").unwrap();
+    fs::set_permissions(dir.join("a\"b"), fs::Permissions::from_mode(0o644)).unwrap();
+    std::os::unix::fs::symlink("a\"b", dir.join("l")).unwrap();
+
+    let json = stdout(&minils(&dir, &["--json"]));
+    // mtimes are whatever the clock said, so they're only checked for being numbers
+    let lines: Vec<String> = json
+        .lines()
+        .map(|line| match line.split_once("\"mtime\": ") {
+            Some((before, after)) => {
+                let (mtime, rest) = after.split_once(',').unwrap();
+                assert!(mtime.parse::<u64>().is_ok(), "{line}");
+                format!("{before}\"mtime\": _,{rest}")
+            }
+            None => line.to_string(),
+        })
+        .collect();
+
+    assert_eq!(
+        lines,
+        [
+            "[",
+            r#"  {"name": "a\"b", "type": "file", "size": 2, "mode": "0644", "mtime": _, "target": null},"#,
+            r#"  {"name": "l", "type": "symlink", "size": 3, "mode": "0777", "mtime": _, "target": "a\"b"}"#,
+            "]",
+        ]
+    );
+}
+
+#[test]
+fn reverse_comes_after_time_and_size_sorts() {
+    let dir = fixture("reverse-sorts");
+    for (name, size, hours) in [("old", 30, 3), ("mid", 10, 2), ("new", 20, 1)] {
+        fs::write(dir.join(name), "x".repeat(size)).unwrap();
+        age(&dir.join(name), hours);
+    }
+
+    assert_eq!(stdout(&minils(&dir, &["-1", "-t"])), "new\nmid\nold\n");
+    assert_eq!(stdout(&minils(&dir, &["-1", "-tr"])), "old\nmid\nnew\n");
+    assert_eq!(stdout(&minils(&dir, &["-1", "-S"])), "old\nnew\nmid\n");
+    assert_eq!(stdout(&minils(&dir, &["-1", "-Sr"])), "mid\nnew\nold\n");
+}
+
+#[test]
+fn directory_operands_sort_like_entries() {
+    let dir = fixture("sort-dir-operands");
+    for (name, hours) in [("new", 1), ("zz", 2)] {
+        fs::create_dir(dir.join(name)).unwrap();
+        fs::write(dir.join(name).join(format!("in-{name}")), "").unwrap();
+        age(&dir.join(name), hours);
+    }
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-t", "zz", "new"])),
+        "new:\nin-new\n\nzz:\nin-zz\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-tr", "new", "zz"])),
+        "zz:\nin-zz\n\nnew:\nin-new\n"
+    );
+}
+
+#[test]
+fn ext_lists_only_matching_files() {
+    let dir = fixture("ext");
+    fs::create_dir_all(dir.join("dA/sub")).unwrap();
+    for name in [
+        "main.rs",
+        "LIB.RS",
+        "notes.txt",
+        "Makefile",
+        "dA/inner.rs",
+        "dA/sub/x.txt",
+    ] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--ext=rs"])),
+        "LIB.RS\nmain.rs\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--ext=.rs", "--ext=txt"])),
+        "LIB.RS\nmain.rs\nnotes.txt\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-R", "--ext=rs"])),
+        "LIB.RS\nmain.rs\n\n./dA:\ninner.rs\n\n./dA/sub:\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["--tree", "--ext=rs"])),
+        ".\n├── dA\n│   ├── inner.rs\n│   └── sub\n├── LIB.RS\n└── main.rs\n"
+    );
+}
+
+#[test]
+fn help_and_version_win_wherever_they_are() {
+    let dir = fixture("help");
+
+    for args in [
+        &["-l", "--help"][..],
+        &["--help", "extra"],
+        &["missing", "-?"],
+    ] {
+        let output = minils(&dir, args);
+        assert!(output.status.success(), "{args:?}");
+        assert!(
+            stdout(&output).starts_with("List directory contents."),
+            "{args:?}"
+        );
+    }
+
+    let output = minils(&dir, &["-l", "--version", "missing"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("minils - "));
+}
+
+#[test]
+fn unsorted_listings_keep_the_filesystem_order() {
+    let dir = fixture("unsorted");
+    for name in ["m", "z", "a", "q", "b", "y", "c"] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+
+    let mut filesystem_order: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    let expected: String = filesystem_order
+        .iter()
+        .map(|name| format!("{name}\n"))
+        .collect();
+    filesystem_order.sort();
+    let sorted: String = filesystem_order
+        .iter()
+        .map(|name| format!("{name}\n"))
+        .collect();
+
+    for args in [
+        &["-1", "-U"][..],
+        &["--raw", "-U"],
+        &["-1", "--sort=none", "-r"],
+    ] {
+        assert_eq!(stdout(&minils(&dir, args)), expected, "{args:?}");
+    }
+    assert_eq!(stdout(&minils(&dir, &["-1"])), sorted);
+}
+
+#[test]
+fn no_operand_lists_the_current_directory() {
+    let dir = nested_fixture("default-operand");
+
+    for args in [
+        &["-1"][..],
+        &["-l"],
+        &["-R"],
+        &["--hyperlink", "--color=always"],
+    ] {
+        let with_dot: Vec<&str> = args.iter().copied().chain(["."]).collect();
+        assert_eq!(
+            stdout(&minils(&dir, args)),
+            stdout(&minils(&dir, &with_dot)),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn names_sort_ignoring_case_by_default() {
+    let dir = fixture("name-sort");
+    touch(&dir, &["b.txt", "A.txt", "c.txt"]);
+
+    assert_eq!(stdout(&minils(&dir, &["-1"])), "A.txt\nb.txt\nc.txt\n");
+}
+
+#[test]
+fn reverse_flips_the_name_order() {
+    let dir = fixture("reverse");
+    touch(&dir, &["a.txt", "b.txt", "c.txt"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-r"])),
+        "c.txt\nb.txt\na.txt\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--reverse"])),
+        "c.txt\nb.txt\na.txt\n"
+    );
+}
+
+#[test]
+fn size_sort_puts_the_largest_first() {
+    let dir = fixture("size-sort");
+    for (name, size) in [("ten", 10), ("thousand", 1000), ("fifty", 50)] {
+        fs::write(dir.join(name), "x".repeat(size)).unwrap();
+    }
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-S"])),
+        "thousand\nfifty\nten\n"
+    );
+}
+
+#[test]
+fn extension_sort_puts_names_without_one_first() {
+    let dir = fixture("extension-sort");
+    touch(&dir, &["c.txt", "Makefile", "b.rs", ".bashrc", "a.rs"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-A", "-X"])),
+        ".bashrc\nMakefile\na.rs\nb.rs\nc.txt\n"
+    );
+}
+
+#[test]
+fn directories_first_keeps_each_group_sorted() {
+    let dir = fixture("dirs-first");
+    touch(&dir, &["b", "D", "a"]);
+    for name in ["y", "C", "x"] {
+        fs::create_dir(dir.join(name)).unwrap();
+    }
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--group-directories-first"])),
+        "C\nx\ny\na\nb\nD\n"
+    );
+}
+
+#[test]
+fn lists_a_directory_of_thousands_of_entries() {
+    let dir = fixture("many");
+    let mut names: Vec<String> = (0..5000).map(|i| format!("entry{i:04}")).collect();
+    for name in &names {
+        fs::write(dir.join(name), "").unwrap();
+    }
+    names.sort();
+
+    let output = minils(&dir, &["-1"]);
+    let expected: String = names.iter().map(|name| format!("{name}\n")).collect();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn a_broken_link_doesnt_hide_its_siblings() {
+    let dir = fixture("broken-link");
+    touch(&dir, &["a", "z"]);
+    std::os::unix::fs::symlink("missing", dir.join("m")).unwrap();
+
+    let output = minils(&dir, &["-1"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "a\nm\nz\n");
+
+    // Following it fails, which is reported, but everything is still listed
+    let output = minils(&dir, &["-1", "-L"]);
+    assert_eq!(stdout(&output), "a\nm\nz\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: following link './m': No such file or directory\n"
+    );
+}
+
+/// A directory, an executable, a plain file and a link, which all get colored differently
+fn colorful_fixture(name: &str) -> PathBuf {
+    let dir = fixture(name);
+    fs::create_dir(dir.join("dir")).unwrap();
+    touch(&dir, &["plain", "script"]);
+    fs::set_permissions(dir.join("script"), fs::Permissions::from_mode(0o755)).unwrap();
+    std::os::unix::fs::symlink("plain", dir.join("link")).unwrap();
+    dir
+}
+
+#[test]
+fn no_escapes_without_color() {
+    let dir = colorful_fixture("no-color");
+
+    for args in [&["-l"][..], &["--color=never"], &["--color=never", "-l"]] {
+        assert!(!stdout(&minils(&dir, args)).contains('\x1b'), "{args:?}");
+    }
+    assert!(stdout(&minils(&dir, &["--color=always"])).contains('\x1b'));
+}
+
+#[test]
+fn no_color_is_overridden_by_color_always() {
+    let dir = colorful_fixture("no-color-env");
+
+    let plain = minils_with_env(&dir, &["-1"], &[("NO_COLOR", "1")]);
+    assert_eq!(stdout(&plain), "dir\nlink\nplain\nscript\n");
+
+    let forced = minils_with_env(&dir, &["-1", "--color=always"], &[("NO_COLOR", "1")]);
+    assert!(stdout(&forced).contains('\x1b'));
+}
+
+#[test]
+fn classify_marks_directories_executables_and_links() {
+    let dir = colorful_fixture("classify");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-F"])),
+        "dir/\nlink@\nplain\nscript*\n"
+    );
+}
+
+#[test]
+fn long_mode_shows_the_modification_time() {
+    let dir = fixture("mtime-column");
+    touch(&dir, &["new", "old"]);
+    // 2020-03-05 12:00 UTC
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_583_409_600);
+    fs::File::open(dir.join("old"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+
+    let listing = stdout(&minils_with_env(&dir, &["-l"], &[("TZ", "UTC")]));
+    let lines: Vec<&str> = listing.lines().collect();
+    let column = lines[1].find("Modified").unwrap();
+
+    // Both formats are twelve characters wide, starting under the header
+    let new = &lines[2][column..];
+    assert_eq!(new.len(), "Mon DD HH:MM  new".len(), "{new:?}");
+    assert_eq!(&new[9..10], ":");
+    assert!(new.ends_with("  new"));
+    assert_eq!(&lines[3][column..], "Mar  5  2020  old");
+}
+
+#[test]
+fn inode_column_lines_up() {
+    let dir = fixture("inode");
+    touch(&dir, &["a", "b"]);
+
+    let listing = stdout(&minils(&dir, &["-l", "-i"]));
+    let lines: Vec<&str> = listing.lines().skip(2).collect();
+    assert_eq!(lines.len(), 2);
+
+    for (line, name) in lines.iter().zip(["a", "b"]) {
+        let inode = fs::metadata(dir.join(name)).unwrap().ino();
+        assert_eq!(line.split_whitespace().next(), Some(&*inode.to_string()));
+        assert!(line.ends_with(&format!("  {name}")));
+    }
+    assert_eq!(lines[0].find("-rw"), lines[1].find("-rw"));
+}
+
+#[test]
+fn hard_links_show_their_count() {
+    let dir = fixture("hard-link");
+    touch(&dir, &["lone", "original"]);
+    fs::hard_link(dir.join("original"), dir.join("twin")).unwrap();
+
+    let listing = stdout(&minils(&dir, &["-l"]));
+    let links: Vec<&str> = listing
+        .lines()
+        .skip(2)
+        .map(|line| line.split_whitespace().nth(1).unwrap())
+        .collect();
+
+    assert_eq!(links, ["1", "2", "2"]);
+}
+
+#[test]
+fn bytes_shows_exact_sizes() {
+    let dir = fixture("bytes");
+    fs::write(dir.join("big"), vec![0; 1_234_567]).unwrap();
+
+    let listing = stdout(&minils(&dir, &["-l", "--bytes"]));
+    let line = listing.lines().last().unwrap();
+    assert_eq!(line.split_whitespace().nth(4), Some("1234567"), "{line}");
+}
+
+#[test]
+fn only_dirs_and_only_files() {
+    let dir = colorful_fixture("only-types");
+
+    assert_eq!(stdout(&minils(&dir, &["-1", "-D"])), "dir\n");
+    assert_eq!(stdout(&minils(&dir, &["-1", "-f"])), "plain\nscript\n");
+}
+
+#[test]
+fn dot_and_dot_dot_only_under_all() {
+    let dir = fixture("all-dots");
+    touch(&dir, &[".hidden", "shown"]);
+
+    assert_eq!(stdout(&minils(&dir, &["-1"])), "shown\n");
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-a"])),
+        ".\n..\n.hidden\nshown\n"
+    );
+    assert_eq!(stdout(&minils(&dir, &["-1", "-A"])), ".hidden\nshown\n");
+}
+
+#[test]
+fn ignore_skips_matching_names() {
+    let dir = fixture("ignore");
+    touch(&dir, &["main.c", "main.o", "util.o"]);
+
+    for args in [&["-1", "-I", "*.o"][..], &["-1", "--ignore=*.o"]] {
+        assert_eq!(stdout(&minils(&dir, args)), "main.c\n", "{args:?}");
+    }
+}
+
+#[test]
+fn list_dirs_shows_the_directory_itself() {
+    let dir = fixture("list-dirs");
+    fs::create_dir(dir.join("src")).unwrap();
+    touch(&dir, &["src/lib.rs", "src/main.rs"]);
+
+    assert_eq!(stdout(&minils(&dir, &["-d", "src"])), "src\n");
+
+    let listing = stdout(&minils(&dir, &["-ld", "src"]));
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2, "{listing}");
+    assert!(lines[0].starts_with("Permissions"));
+    assert!(lines[1].starts_with('d') && lines[1].ends_with("  src"));
+}
+
+#[test]
+fn tree_connectors_continue_past_nested_directories() {
+    let dir = nested_fixture("tree");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["--tree"])),
+        ".\n\
+         ├── a\n\
+         ├── b\n\
+         └── d\n    \
+             ├── e\n    \
+             │   └── z\n    \
+             ├── x\n    \
+             └── y\n"
+    );
+}
+
+#[test]
+fn total_counts_files_directories_and_bytes() {
+    let dir = fixture("total");
+    fs::write(dir.join("a"), vec![0; 100]).unwrap();
+    fs::write(dir.join("b"), vec![0; 23]).unwrap();
+    fs::create_dir(dir.join("d1")).unwrap();
+    fs::create_dir(dir.join("d2")).unwrap();
+
+    let listing = stdout(&minils(&dir, &["-1", "--total"]));
+    assert_eq!(
+        listing,
+        "a\nb\nd1\nd2\n2 files, 2 directories, 123B total\n"
+    );
+
+    let listing = stdout(&minils(&dir, &["-1", "--total", "-f", "--max-entries=1"]));
+    assert!(
+        listing.ends_with("1 file, 0 directories, 100B total\n"),
+        "{listing}"
+    );
+}
+
+#[test]
+fn long_mode_total_counts_allocated_kilobytes() {
+    let dir = fixture("total-blocks");
+    fs::write(dir.join("big"), vec![1; 50_000]).unwrap();
+    fs::write(dir.join("small"), "x").unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+
+    let sectors: u64 = ["big", "small", "sub"]
+        .iter()
+        .map(|name| fs::symlink_metadata(dir.join(name)).unwrap().blocks())
+        .sum();
+    assert!(sectors > 0);
+
+    let listing = stdout(&minils(&dir, &["-l"]));
+    assert_eq!(
+        listing.lines().next(),
+        Some(&*format!("total {}", (sectors * 512).div_ceil(1024)))
+    );
+}
+
+#[test]
+fn size_shows_allocated_blocks_before_names() {
+    let dir = fixture("size-blocks");
+    fs::write(dir.join("big"), vec![1; 50_000]).unwrap();
+    touch(&dir, &["empty"]);
+
+    let listing = stdout(&minils(&dir, &["-s", "-1"]));
+    let lines: Vec<(u64, &str)> = listing
+        .lines()
+        .map(|line| {
+            let (blocks, name) = line.trim_start().split_once("  ").unwrap();
+            (blocks.parse().unwrap(), name)
+        })
+        .collect();
+
+    // 50000 bytes take at least 49 kilobytes, and no more than a few more than that
+    assert_eq!(lines[0].1, "big");
+    assert!((49..=64).contains(&lines[0].0), "{listing}");
+    assert_eq!(lines[1], (0, "empty"));
+}
+
+#[test]
+fn names_that_arent_utf8_still_list() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = fixture("not-utf8");
+    fs::write(dir.join(OsStr::from_bytes(b"bad\xffname")), "").unwrap();
+    touch(&dir, &["ok"]);
+
+    for args in [&["-1"][..], &["-l"]] {
+        let output = minils(&dir, args);
+        assert!(output.status.success(), "{args:?}");
+        let listing = stdout(&output);
+        assert!(listing.contains("bad\u{fffd}name\n"), "{args:?}: {listing}");
+        assert!(listing.contains("ok\n"), "{args:?}: {listing}");
+    }
+}
+
+#[test]
+fn dereference_describes_link_targets() {
+    let dir = fixture("dereference");
+    fs::create_dir(dir.join("dir")).unwrap();
+    fs::write(dir.join("file"), "12345").unwrap();
+    std::os::unix::fs::symlink("dir", dir.join("to-dir")).unwrap();
+    std::os::unix::fs::symlink("file", dir.join("to-file")).unwrap();
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-F"])),
+        "dir/\nfile\nto-dir@\nto-file@\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-F", "-L"])),
+        "dir/\nfile\nto-dir/\nto-file\n"
+    );
+
+    let listing = stdout(&minils(&dir, &["-l", "-L"]));
+    let to_dir = listing
+        .lines()
+        .find(|line| line.ends_with("to-dir"))
+        .unwrap();
+    let to_file = listing
+        .lines()
+        .find(|line| line.ends_with("to-file"))
+        .unwrap();
+    assert!(to_dir.starts_with('d'), "{to_dir}");
+    assert!(
+        to_file.starts_with('-') && to_file.contains(" 5B "),
+        "{to_file}"
+    );
+}
+
+#[test]
+fn broken_links_get_the_orphan_color() {
+    let dir = fixture("orphan");
+    touch(&dir, &["target"]);
+    std::os::unix::fs::symlink("missing", dir.join("broken")).unwrap();
+    std::os::unix::fs::symlink("target", dir.join("working")).unwrap();
+
+    let listing = stdout(&minils(&dir, &["-1", "--color=always"]));
+    assert!(
+        listing.contains("\x1b[1;31;40mbroken\x1b[0m\n"),
+        "{listing:?}"
+    );
+    assert!(
+        listing.contains("\x1b[1;96mworking\x1b[0m\n"),
+        "{listing:?}"
+    );
+
+    let listing = stdout(&minils_with_env(
+        &dir,
+        &["-1", "--color=always"],
+        &[("LS_COLORS", "or=01;05;37;41")],
+    ));
+    assert!(
+        listing.contains("\x1b[01;05;37;41mbroken\x1b[0m\n"),
+        "{listing:?}"
+    );
+}
+
+#[test]
+fn long_columns_line_up_across_size_magnitudes() {
+    let dir = fixture("long-align");
+    for (name, size) in [("huge", 5_000_000), ("mid", 50_000), ("tiny", 1)] {
+        fs::write(dir.join(name), vec![0; size]).unwrap();
+    }
+
+    for args in [&["-l"][..], &["-l", "--bytes"], &["-l", "-h"]] {
+        let listing = stdout(&minils(&dir, args));
+        let lines: Vec<&str> = listing.lines().skip(1).collect();
+        let time = lines[0].find("Modified").unwrap();
+        let name = lines[0].find("Name").unwrap();
+
+        for line in &lines[1..] {
+            let bytes = line.as_bytes();
+            // Sizes end right before the gap in front of the time, which starts under its header
+            assert_ne!(bytes[time - 3], b' ', "{args:?}\n{listing}");
+            assert_eq!(&line[time - 2..time], "  ", "{args:?}\n{listing}");
+            assert_ne!(bytes[time], b' ', "{args:?}\n{listing}");
+            assert_eq!(&line[name - 2..name], "  ", "{args:?}\n{listing}");
+            assert_ne!(bytes[name], b' ', "{args:?}\n{listing}");
+        }
+    }
+}
+
+#[test]
+fn a_file_operand_gets_one_header() {
+    let dir = fixture("file-header");
+    touch(&dir, &["file"]);
+
+    let listing = stdout(&minils(&dir, &["-l", "file"]));
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2, "{listing}");
+    assert!(lines[0].starts_with("Permissions"));
+    assert_eq!(listing.matches("Permissions").count(), 1);
+    assert!(lines[1].ends_with("  file"));
+}
+
+#[test]
+fn a_file_operand_looks_like_it_does_in_its_directory() {
+    let dir = fixture("file-operand");
+    fs::create_dir(dir.join("only")).unwrap();
+    fs::write(dir.join("only/file"), "12345").unwrap();
+    fs::set_permissions(dir.join("only/file"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    for args in [
+        &["-1"][..],
+        &["-l"],
+        &["-l", "-i", "-o"],
+        &["-F", "--color=always"],
+    ] {
+        let in_dir = stdout(&minils(&dir, &[args, &["only"]].concat()));
+        let operand = stdout(&minils(&dir.join("only"), &[args, &["file"]].concat()));
+
+        // Only directories get a total line
+        let in_dir = match in_dir.split_once('\n') {
+            Some((total, rest)) if total.starts_with("total ") => rest,
+            _ => &in_dir,
+        };
+        assert_eq!(operand, in_dir, "{args:?}");
+    }
+}
+
+#[test]
+fn shell_quoting_handles_spaces_and_newlines() {
+    let dir = fixture("shell-quoting");
+    touch(&dir, &["a b", "new\nline", "plain"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--quoting-style=shell"])),
+        "'a b'\n'new?line'\nplain\n"
+    );
+}
+
+#[test]
+fn the_last_layout_option_wins() {
+    let dir = fixture("last-layout");
+    touch(&dir, &["a", "b"]);
+
+    let one_per_line = "a\nb\n";
+    assert_eq!(stdout(&minils(&dir, &["-l", "-1"])), one_per_line);
+    assert_eq!(stdout(&minils(&dir, &["-G", "-1"])), one_per_line);
+    assert!(stdout(&minils(&dir, &["-1", "-l"])).starts_with("total "));
+}
+
+#[test]
+fn access_time_sorts_and_shows_under_u() {
+    let dir = fixture("atime");
+    touch(&dir, &["a", "b"]);
+    let day = |day: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(day * 24 * 60 * 60);
+    // a was modified first but read last, and b the other way around
+    for (name, modified, accessed) in [("a", day(1), day(20_000)), ("b", day(10_000), day(2))] {
+        let times = fs::FileTimes::new()
+            .set_modified(modified)
+            .set_accessed(accessed);
+        fs::File::open(dir.join(name))
+            .unwrap()
+            .set_times(times)
+            .unwrap();
+    }
+
+    assert_eq!(stdout(&minils(&dir, &["-1", "-t"])), "b\na\n");
+    assert_eq!(stdout(&minils(&dir, &["-1", "-tu"])), "a\nb\n");
+
+    let listing = stdout(&minils_with_env(
+        &dir,
+        &["-l", "-tu", "--time-style=long-iso"],
+        &[("TZ", "UTC")],
+    ));
+    let lines: Vec<&str> = listing.lines().skip(1).collect();
+    assert!(lines[0].contains("Accessed"), "{listing}");
+    assert!(lines[1].contains("2024-10-04 00:00  a"), "{listing}");
+    assert!(lines[2].contains("1970-01-03 00:00  b"), "{listing}");
+}
+
+#[test]
+fn max_depth_stops_recursing() {
+    let dir = nested_fixture("max-depth");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-R", "-1", "--max-depth=2"])),
+        "a\nb\nd\n\n./d:\ne\nx\ny\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-R", "-1", "--max-depth=3"])),
+        stdout(&minils(&dir, &["-R", "-1"]))
+    );
+}
+
+#[test]
+fn case_sensitive_puts_uppercase_first() {
+    let dir = fixture("case-sensitive");
+    touch(&dir, &["Apple", "Banana", "apple", "banana"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--case-sensitive"])),
+        "Apple\nBanana\napple\nbanana\n"
+    );
+}
+
+#[test]
+fn only_dirs_recurses_through_every_level() {
+    let dir = nested_fixture("only-dirs-recurse");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-DR", "-1"])),
+        "d\n\n./d:\ne\n\n./d/e:\n"
+    );
+}
+
+#[test]
+fn escape_writes_c_style_escapes() {
+    let dir = fixture("escape");
+    touch(&dir, &["tab\there", "bell\x07"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-b"])),
+        "bell\\007\ntab\\there\n"
+    );
+}
+
+#[test]
+fn no_header_leaves_out_the_column_names() {
+    let dir = fixture("no-header");
+    touch(&dir, &["a"]);
+
+    let with = stdout(&minils(&dir, &["-l"]));
+    let without = stdout(&minils(&dir, &["-l", "--no-header"]));
+
+    assert_eq!(with.lines().count(), 3);
+    assert!(with.lines().nth(1).unwrap().starts_with("Permissions"));
+    assert_eq!(without.lines().count(), 2);
+    assert!(!without.contains("Permissions"));
+    assert_eq!(without.lines().nth(1), with.lines().nth(2));
+}
+
+#[test]
+fn a_missing_path_is_an_error() {
+    let dir = fixture("missing");
+
+    let output = minils(&dir, &["missing"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: cannot access 'missing': No such file or directory\n"
+    );
+}
+
+#[test]
+fn a_missing_path_doesnt_stop_the_others() {
+    let dir = fixture("missing-and-valid");
+    fs::create_dir(dir.join("real")).unwrap();
+    touch(&dir, &["real/inside"]);
+
+    let output = minils(&dir, &["-1", "missing", "real"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "real:\ninside\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: cannot access 'missing': No such file or directory\n"
+    );
+}
+
+#[test]
+fn dereference_command_line_only_follows_operands() {
+    let dir = fixture("dereference-operands");
+    fs::create_dir(dir.join("real")).unwrap();
+    touch(&dir, &["real/inside"]);
+    std::os::unix::fs::symlink("real", dir.join("link")).unwrap();
+
+    // Long mode shows the link itself unless it's followed
+    let listing = stdout(&minils(&dir, &["-l", "link"]));
+    assert!(listing.ends_with("  link -> real\n"), "{listing}");
+    let listing = stdout(&minils(&dir, &["-l", "-H", "link"]));
+    assert!(listing.ends_with("  inside\n"), "{listing}");
+
+    // Inside a listed directory, it stays a link
+    assert_eq!(stdout(&minils(&dir, &["-1", "-F", "-H"])), "link@\nreal/\n");
+}
+
+#[test]
+fn group_directories_last_puts_files_first() {
+    let dir = fixture("dirs-last");
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("c")).unwrap();
+    touch(&dir, &["b", "d"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--group-directories-last"])),
+        "b\nd\na\nc\n"
+    );
+
+    let output = minils(
+        &dir,
+        &["--group-directories-first", "--group-directories-last"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("minils: --group-directories-first and --group-directories-last can't"));
+}
+
+#[test]
+fn author_is_the_owner_in_its_own_column() {
+    let dir = fixture("author");
+    touch(&dir, &["a", "b"]);
+
+    let listing = stdout(&minils(&dir, &["-l", "--author"]));
+    let lines: Vec<&str> = listing.lines().skip(1).collect();
+    let column = lines[0].find("Author").unwrap();
+
+    for line in &lines[1..] {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(fields[4], fields[2], "{line}");
+        assert!(line[column..].starts_with(fields[4]), "{listing}");
+    }
+}
+
+#[test]
+fn slash_marks_only_directories() {
+    let dir = colorful_fixture("slash");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "-p"])),
+        "dir/\nlink\nplain\nscript\n"
+    );
+}
+
+#[test]
+fn files_from_lists_the_paths_in_a_file() {
+    let dir = fixture("files-from");
+    fs::create_dir(dir.join("sub")).unwrap();
+    touch(&dir, &["one", "two", "sub/inner"]);
+    fs::write(dir.join("paths"), "one\ntwo\nsub\n").unwrap();
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--files-from=paths"])),
+        "one\ntwo\n\nsub:\ninner\n"
+    );
+}
+
+#[test]
+fn max_entries_says_how_many_were_left_out() {
+    let dir = fixture("max-entries");
+    touch(&dir, &["a", "b", "c", "d", "e"]);
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--max-entries=2"])),
+        "a\nb\n... and 3 more\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--max-entries=5"])),
+        "a\nb\nc\nd\ne\n"
+    );
+}
+
+#[test]
+fn newer_than_and_older_than_split_on_the_mtime() {
+    let dir = fixture("newer-older");
+    touch(&dir, &["hour", "month", "now"]);
+    age(&dir.join("hour"), 1);
+    age(&dir.join("month"), 30 * 24);
+
+    assert_eq!(stdout(&minils(&dir, &["-1", "--newer-than=30m"])), "now\n");
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--newer-than=1d"])),
+        "hour\nnow\n"
+    );
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--older-than=30m"])),
+        "hour\nmonth\n"
+    );
+    assert_eq!(stdout(&minils(&dir, &["-1", "--older-than=1w"])), "month\n");
+}
+
+#[test]
+fn size_bounds_keep_files_in_range_and_every_directory() {
+    let dir = fixture("size-bounds");
+    for (name, size) in [("large", 5_000_000), ("medium", 50_000), ("small", 500)] {
+        fs::write(dir.join(name), vec![0; size]).unwrap();
+    }
+    fs::create_dir(dir.join("sub")).unwrap();
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--min-size=1K", "--max-size=1M"])),
+        "medium\nsub\n"
+    );
+}
+
+#[test]
+fn du_totals_everything_under_a_directory() {
+    let dir = fixture("du");
+    fs::create_dir_all(dir.join("d/e")).unwrap();
+    fs::write(dir.join("d/x"), vec![0; 1000]).unwrap();
+    fs::write(dir.join("d/e/z"), vec![0; 234]).unwrap();
+
+    let listing = stdout(&minils(&dir, &["-l", "--du", "--bytes"]));
+    let line = listing.lines().last().unwrap();
+    assert_eq!(line.split_whitespace().nth(4), Some("1234"), "{line}");
+}
+
+#[test]
+fn reverse_keeps_directories_first() {
+    let dir = fixture("dirs-first-reverse");
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::create_dir(dir.join("b")).unwrap();
+    touch(&dir, &["x", "y"]);
+
+    assert_eq!(
+        stdout(&minils(
+            &dir,
+            &["-1", "-p", "--group-directories-first", "-r"]
+        )),
+        "b/\na/\ny\nx\n"
+    );
+}
+
+#[test]
+fn allocated_size_is_smaller_for_sparse_files() {
+    let dir = fixture("sparse");
+    let file = fs::File::create(dir.join("sparse")).unwrap();
+    file.set_len(10 * 1024 * 1024).unwrap();
+
+    let size = |args: &[&str]| -> u64 {
+        let listing = stdout(&minils(&dir, args));
+        let line = listing.lines().last().unwrap();
+        line.split_whitespace().nth(4).unwrap().parse().unwrap()
+    };
+
+    assert_eq!(size(&["-l", "--bytes"]), 10 * 1024 * 1024);
+    assert!(size(&["-l", "--bytes", "--allocated-size"]) < 10 * 1024 * 1024);
+}
+
+#[test]
+fn zero_ends_names_with_nul_bytes() {
+    let dir = colorful_fixture("zero");
+
+    let output = minils(&dir, &["--zero", "--color=always", "-l"]);
+    assert_eq!(output.stdout, b"dir\0link\0plain\0script\0");
+}
+
+#[test]
+fn link_target_shows_arrows_in_one_per_line_mode() {
+    let dir = colorful_fixture("link-target");
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--link-target"])),
+        "dir\nlink -> plain\nplain\nscript\n"
+    );
+}
+
+#[test]
+fn equal_sizes_fall_back_to_the_name() {
+    let dir = fixture("size-ties");
+    for (name, size) in [("b", 10), ("big", 100), ("a", 10)] {
+        fs::write(dir.join(name), vec![0; size]).unwrap();
+    }
+
+    assert_eq!(stdout(&minils(&dir, &["-1", "-S"])), "big\na\nb\n");
+}
+
+#[test]
+fn dir_counts_shows_the_number_of_entries() {
+    let dir = fixture("dir-counts");
+    fs::create_dir(dir.join("three")).unwrap();
+    touch(&dir, &["three/a", "three/b", "three/c", "three/.hidden"]);
+
+    // Hidden entries only count under -a
+    let listing = stdout(&minils(&dir, &["-l", "--dir-counts"]));
+    let line = listing.lines().last().unwrap();
+    assert!(line.contains(" 3 items  "), "{line}");
+
+    let listing = stdout(&minils(&dir, &["-l", "--dir-counts", "-a"]));
+    let line = listing.lines().last().unwrap();
+    assert!(line.contains(" 4 items  "), "{line}");
+}
+
+#[test]
+fn raw_prints_only_the_sorted_names() {
+    let dir = colorful_fixture("raw");
+
+    let output = minils(&dir, &["--raw", "-l", "-F", "--color=always", "-i"]);
+    assert_eq!(stdout(&output), "dir\nlink\nplain\nscript\n");
+}
+
+#[test]
+fn only_links_lists_just_the_symlinks() {
+    let dir = colorful_fixture("only-links");
+    std::os::unix::fs::symlink("dir", dir.join("dir-link")).unwrap();
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-1", "--only-links"])),
+        "dir-link\nlink\n"
+    );
+
+    let output = minils(&dir, &["--only-links", "-D"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: --only-dirs and --only-links can't be used together. \
+         For help, try running 'minils --help'\n"
+    );
+}
+
+#[test]
+fn find_prints_the_paths_of_matching_names() {
+    let dir = fixture("find");
+    fs::create_dir_all(dir.join("src/bin")).unwrap();
+    touch(
+        &dir,
+        &[
+            "build.rs",
+            "README.md",
+            "src/lib.rs",
+            "src/notes.txt",
+            "src/bin/main.rs",
+        ],
+    );
+
+    assert_eq!(
+        stdout(&minils(&dir, &["--find=*.rs"])),
+        "./build.rs\n./src/bin/main.rs\n./src/lib.rs\n"
+    );
+}
+
+#[test]
+fn summary_counts_types_and_adds_up_sizes() {
+    let dir = fixture("summary");
+    fs::write(dir.join("big"), vec![0; 300]).unwrap();
+    fs::write(dir.join("small"), vec![0; 45]).unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+    std::os::unix::fs::symlink("big", dir.join("link")).unwrap();
+
+    let summary = stdout(&minils(&dir, &["--summary"]));
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(
+        lines[0],
+        "4 entries: 2 files, 1 directory, 1 symlink, 0 other"
+    );
+    // The link counts as its own three bytes
+    assert_eq!(lines[1], "Total size: 348B");
+    assert_eq!(lines[2], "Largest file: big (300B)");
+}
+
+#[test]
+fn empty_and_single_entry_listings_end_cleanly() {
+    let dir = fixture("empty");
+
+    for args in [&[][..], &["-1"], &["-x"], &["-m"]] {
+        let output = minils(&dir, args);
+        assert!(output.status.success(), "{args:?}");
+        assert_eq!(output.stdout, b"", "{args:?}");
+    }
+    assert_eq!(stdout(&minils(&dir, &["-l"])), "total 0\n");
+
+    touch(&dir, &["only"]);
+    for args in [&[][..], &["-x"], &["-m"], &["-w", "200"]] {
+        assert_eq!(stdout(&minils(&dir, args)), "only\n", "{args:?}");
+    }
+}
+
+#[test]
+fn all_with_recurse_doesnt_descend_into_dot_entries() {
+    let dir = fixture("all-recurse");
+    fs::create_dir_all(dir.join("s/t")).unwrap();
+    touch(&dir, &["s/t/u"]);
+
+    let output = minils(&dir, &["-aR", "-1"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        ".\n..\ns\n\n./s:\n.\n..\nt\n\n./s/t:\n.\n..\nu\n"
+    );
+}
+
+#[test]
+fn no_filename_leaves_out_the_name_column() {
+    let dir = fixture("no-filename");
+    touch(&dir, &["named"]);
+
+    let listing = stdout(&minils(&dir, &["-l", "--no-filename"]));
+    assert!(!listing.contains("Name"), "{listing}");
+    assert!(!listing.contains("named"), "{listing}");
+    assert_eq!(listing.lines().count(), 3);
+
+    let output = minils(&dir, &["--no-filename"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: --no-filename only works with -l. For help, try running 'minils --help'\n"
+    );
+}
+
+#[test]
+fn hyperlinks_wrap_names_in_osc_8() {
+    let dir = fixture("hyperlink");
+    touch(&dir, &["a b%.txt"]);
+
+    let listing = stdout(&minils(&dir, &["-1", "--hyperlink", "--color=always"]));
+    assert_eq!(
+        listing,
+        format!(
+            "\x1b]8;;file://{}/a%20b%25.txt\x1b\\\x1b[1ma b%.txt\x1b[0m\x1b]8;;\x1b\\\n",
+            dir.display()
+        )
+    );
+
+    // Like colors, not when piped
+    assert_eq!(stdout(&minils(&dir, &["-1", "--hyperlink"])), "a b%.txt\n");
+}
+
+#[test]
+fn doubling_the_block_size_halves_the_block_counts() {
+    let dir = fixture("block-size");
+    fs::write(dir.join("data"), vec![1; 64 * 1024]).unwrap();
+
+    let blocks = |args: &[&str], vars: &[(&str, &str)]| -> u64 {
+        let listing = stdout(&minils_with_env(&dir, args, vars));
+        let (blocks, _) = listing.trim_start().split_once("  ").unwrap();
+        blocks.parse().unwrap()
+    };
+
+    let kilobytes = blocks(&["-s", "-1"], &[]);
+    assert!(kilobytes >= 64);
+    assert_eq!(
+        blocks(&["-s", "-1", "--blocksize=2K"], &[]),
+        kilobytes.div_ceil(2)
+    );
+    assert_eq!(
+        blocks(&["-s", "-1"], &[("BLOCK_SIZE", "2K")]),
+        kilobytes.div_ceil(2)
+    );
+
+    let total = |args: &[&str], vars: &[(&str, &str)]| {
+        let listing = stdout(&minils_with_env(&dir, args, vars));
+        listing.lines().next().unwrap().to_string()
+    };
+    assert_eq!(total(&["-l"], &[]), format!("total {kilobytes}"));
+    assert_eq!(
+        total(&["-l"], &[("BLOCK_SIZE", "2048")]),
+        format!("total {}", kilobytes.div_ceil(2))
+    );
+}
+
+#[test]
+fn show_target_works_without_dereferencing() {
+    let dir = fixture("show-target");
+    touch(&dir, &["file"]);
+    std::os::unix::fs::symlink("file", dir.join("link")).unwrap();
+
+    assert_eq!(
+        stdout(&minils(&dir, &["-x", "-w", "200", "--show-target"])),
+        "file          link -> file\n"
+    );
+    let listing = stdout(&minils(&dir, &["-l", "--show-target"]));
+    assert!(listing.ends_with("  link -> file\n"), "{listing}");
+
+    // -L alone describes the target instead, which isn't a link
+    assert_eq!(stdout(&minils(&dir, &["-1", "-L"])), "file\nlink\n");
+    let listing = stdout(&minils(&dir, &["-l", "-L"]));
+    let last = listing.lines().last().unwrap();
+    assert!(
+        last.starts_with('-') && last.ends_with("  link"),
+        "{listing}"
+    );
+}
+
+#[test]
+fn an_unreadable_directory_doesnt_stop_the_others() {
+    // Root can read anything, so there'd be nothing to report
+    if fs::metadata("/proc/self").is_ok_and(|proc| proc.uid() == 0) {
+        eprintln!("skipped: running as root");
+        return;
+    }
+
+    let dir = fixture("unreadable");
+    fs::create_dir(dir.join("locked")).unwrap();
+    fs::create_dir(dir.join("open")).unwrap();
+    touch(&dir, &["open/inside"]);
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+
+    let output = minils(&dir, &["-1", "locked", "open"]);
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "minils: cannot open directory 'locked': Permission denied\n"
+    );
+    assert!(stdout(&output).ends_with("open:\ninside\n"));
+}